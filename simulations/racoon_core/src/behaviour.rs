@@ -0,0 +1,147 @@
+//! Validators behaviours of event-driven simulations.
+
+use crate::chain::Block;
+use serde::Deserialize;
+use std::{fmt::Debug, sync::Arc};
+
+/// How a validator publishes a block it created.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Publication {
+    /// Sent to every validator right away.
+    Now,
+    /// Sent to every validator after given number of ticks.
+    Delayed(u64),
+    /// Kept private until released.
+    Withheld,
+}
+
+/// Strategy followed by a validator.
+pub trait Behaviour: Debug + Send + Sync {
+    /// Name of the strategy, used to group validators in reports.
+    fn name(&self) -> &'static str;
+
+    /// Check if the validator accepts to build on top of given block.
+    fn accepts(&self, _block: &Block) -> bool {
+        true
+    }
+
+    /// How a newly created block is published.
+    fn publication(&self) -> Publication {
+        Publication::Now
+    }
+
+    /// Check if withheld blocks should be published, given their amount and
+    /// if the validator just switched to a fork of another validator.
+    fn releases(&self, _withheld: usize, _overtaken: bool) -> bool {
+        true
+    }
+
+    /// Check if the validator signs a second conflicting block each time it
+    /// creates one.
+    fn equivocates(&self) -> bool {
+        false
+    }
+}
+
+/// Follows the protocol.
+#[derive(Debug, Clone)]
+pub struct Honest;
+
+impl Behaviour for Honest {
+    fn name(&self) -> &'static str {
+        "honest"
+    }
+}
+
+/// Keeps its blocks private until it has `blocks` of them, or until another
+/// fork becomes heavier than its own.
+#[derive(Debug, Clone)]
+pub struct Withholding {
+    pub blocks: usize,
+}
+
+impl Behaviour for Withholding {
+    fn name(&self) -> &'static str {
+        "withholding"
+    }
+
+    fn publication(&self) -> Publication {
+        Publication::Withheld
+    }
+
+    fn releases(&self, withheld: usize, overtaken: bool) -> bool {
+        overtaken || withheld >= self.blocks
+    }
+}
+
+/// Signs 2 blocks at each height and sends each one to half of the
+/// validators.
+#[derive(Debug, Clone)]
+pub struct Equivocating;
+
+impl Behaviour for Equivocating {
+    fn name(&self) -> &'static str {
+        "equivocating"
+    }
+
+    fn equivocates(&self) -> bool {
+        true
+    }
+}
+
+/// Publishes its blocks `ticks` after creating them.
+#[derive(Debug, Clone)]
+pub struct Delayed {
+    pub ticks: u64,
+}
+
+impl Behaviour for Delayed {
+    fn name(&self) -> &'static str {
+        "delayed"
+    }
+
+    fn publication(&self) -> Publication {
+        Publication::Delayed(self.ticks)
+    }
+}
+
+/// Refuses to build on top of blocks created by given validator.
+#[derive(Debug, Clone)]
+pub struct Refusing {
+    pub validator: usize,
+}
+
+impl Behaviour for Refusing {
+    fn name(&self) -> &'static str {
+        "refusing"
+    }
+
+    fn accepts(&self, block: &Block) -> bool {
+        block.validator_id != self.validator
+    }
+}
+
+/// Behaviour as written in config files.
+#[derive(Debug, Clone, Deserialize)]
+pub enum BehaviourConfig {
+    Honest,
+    Withholding { blocks: usize },
+    Equivocating,
+    Delayed { ticks: u64 },
+    Refusing { validator: usize },
+}
+
+impl BehaviourConfig {
+    /// Create the configured behaviour.
+    pub fn build(&self) -> Arc<dyn Behaviour> {
+        match self {
+            BehaviourConfig::Honest => Arc::new(Honest),
+            BehaviourConfig::Withholding { blocks } => Arc::new(Withholding { blocks: *blocks }),
+            BehaviourConfig::Equivocating => Arc::new(Equivocating),
+            BehaviourConfig::Delayed { ticks } => Arc::new(Delayed { ticks: *ticks }),
+            BehaviourConfig::Refusing { validator } => Arc::new(Refusing {
+                validator: *validator,
+            }),
+        }
+    }
+}
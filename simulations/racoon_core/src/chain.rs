@@ -0,0 +1,84 @@
+//! Blocks and validators of event-driven simulations.
+
+use crate::behaviour::{Behaviour, Honest};
+use rug::Float;
+use std::{
+    collections::{BTreeMap, BTreeSet},
+    sync::Arc,
+};
+
+/// A block.
+#[derive(Debug, Clone)]
+pub struct Block {
+    /// Height of the block.
+    pub height: u64,
+    /// Shard of the block.
+    pub shard_id: u64,
+    /// ID of the previous block (0 is genesis).
+    pub previous_block_id: u64,
+    /// Validator which created this block.
+    pub validator_id: usize,
+    /// Block weight.
+    pub weight: Float,
+    /// Time at which the block was created.
+    pub time: u64,
+}
+
+/// A validator.
+#[derive(Debug, Clone)]
+pub struct Validator {
+    /// Validator power (% of stake).
+    pub power: Float,
+    /// Current finalized block (won't reorg to a fork not containing this block).
+    pub finalized_block_id: u64,
+    /// Current fork head.
+    pub current_head_id: u64,
+    /// Current fork cumulative weight.
+    pub current_fork_weight: Float,
+    /// ID of blocks on which VDF have been finished with their N+2 block weight.
+    pub finished_vdf: BTreeMap<u64, Float>,
+    /// ID of blocks already received, to ignore duplicated messages.
+    pub received_blocks: BTreeSet<u64>,
+    /// Strategy followed by the validator.
+    pub behaviour: Arc<dyn Behaviour>,
+    /// ID of created blocks not published yet.
+    pub withheld_blocks: Vec<u64>,
+
+    pub latest_created_height: u64,
+}
+
+impl Validator {
+    pub fn from_power(power: Float) -> Self {
+        let precision = power.prec();
+
+        Validator {
+            power,
+            finalized_block_id: 0,
+            current_head_id: 0,
+            current_fork_weight: Float::with_val(precision, 0),
+            finished_vdf: BTreeMap::new(),
+            received_blocks: BTreeSet::new(),
+            behaviour: Arc::new(Honest),
+            withheld_blocks: vec![],
+            latest_created_height: 0,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FLOAT_PRECISION;
+
+    #[test]
+    fn validator_starts_on_genesis() {
+        let validator = Validator::from_power(Float::with_val(FLOAT_PRECISION, 0.25));
+
+        assert_eq!(validator.power, 0.25);
+        assert_eq!(validator.finalized_block_id, 0);
+        assert_eq!(validator.current_head_id, 0);
+        assert_eq!(validator.current_fork_weight, 0);
+        assert_eq!(validator.current_fork_weight.prec(), FLOAT_PRECISION);
+        assert_eq!(validator.behaviour.name(), "honest");
+    }
+}
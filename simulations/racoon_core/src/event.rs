@@ -0,0 +1,69 @@
+//! Timed events for event-driven simulations.
+
+use std::{cmp::Ordering, collections::BinaryHeap};
+
+/// A timed simulation event.
+#[derive(Debug, Clone)]
+pub struct TimedEvent<E> {
+    /// Time at which an event occurs.
+    pub time: u64,
+    /// Validator reacting to this event.
+    pub validator_id: usize,
+    /// Simulation event.
+    pub event: E,
+}
+
+/// Events are ordered by reversed time, so a `BinaryHeap` pops the earliest
+/// event first.
+impl<E> PartialEq for TimedEvent<E> {
+    fn eq(&self, other: &Self) -> bool {
+        self.time == other.time
+    }
+}
+
+impl<E> Eq for TimedEvent<E> {}
+
+impl<E> PartialOrd for TimedEvent<E> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<E> Ord for TimedEvent<E> {
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.time.cmp(&self.time)
+    }
+}
+
+/// Pool of events. They will be executed in order of their `time`.
+pub type EventQueue<E> = BinaryHeap<TimedEvent<E>>;
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn event(time: u64, validator_id: usize) -> TimedEvent<()> {
+        TimedEvent {
+            time,
+            validator_id,
+            event: (),
+        }
+    }
+
+    #[test]
+    fn pops_earliest_first() {
+        let mut queue = EventQueue::new();
+        for (i, time) in [30, 10, 50, 20, 40].iter().enumerate() {
+            queue.push(event(*time, i));
+        }
+
+        let times: Vec<_> = std::iter::from_fn(|| queue.pop()).map(|e| e.time).collect();
+        assert_eq!(times, vec![10, 20, 30, 40, 50]);
+    }
+
+    #[test]
+    fn ordering_ignores_validator() {
+        assert_eq!(event(10, 0), event(10, 1));
+        assert!(event(10, 0) > event(20, 0));
+    }
+}
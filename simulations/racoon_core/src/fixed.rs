@@ -0,0 +1,227 @@
+//! Fixed-point version of the `rand^(1/power)` weight formula.
+//!
+//! Every operation is performed on integers with a fixed amount of fractional
+//! bits, so all validators compute the exact same weight regardless of their
+//! machine, float library or rounding mode.
+
+use rug::{Float, Integer};
+use sha3::{Digest, Sha3_256};
+
+/// Amount of fractional bits of fixed-point numbers.
+pub const FRACTION_BITS: u32 = 64;
+
+/// `1.0` in fixed-point.
+const ONE: u128 = 1 << FRACTION_BITS;
+
+/// `2^(-2^-i)` for `i` in `1..=FRACTION_BITS`, used to compute powers of 2.
+const EXP2_TABLE: [u128; FRACTION_BITS as usize] = exp2_table();
+
+/// Multiply 2 fixed-point numbers (rounded down).
+const fn mul(a: u128, b: u128) -> u128 {
+    let (a1, a0) = (a >> 64, a & (u64::MAX as u128));
+    let (b1, b0) = (b >> 64, b & (u64::MAX as u128));
+
+    ((a1 * b1) << 64) + a1 * b0 + a0 * b1 + ((a0 * b0) >> 64)
+}
+
+/// Integer square root (rounded down).
+const fn isqrt(n: u128) -> u128 {
+    if n < 2 {
+        return n;
+    }
+
+    let mut x = n;
+    let mut y = x.div_ceil(2);
+
+    while y < x {
+        x = y;
+        y = (x + n / x) / 2;
+    }
+
+    x
+}
+
+const fn exp2_table() -> [u128; FRACTION_BITS as usize] {
+    let mut table = [0; FRACTION_BITS as usize];
+
+    // 2^(-1/2) = sqrt(1/2), then each entry is the square root of the previous one.
+    let mut value = ONE / 2;
+    let mut i = 0;
+
+    while i < FRACTION_BITS as usize {
+        value = isqrt(value << FRACTION_BITS);
+        table[i] = value;
+        i += 1;
+    }
+
+    table
+}
+
+/// Compute `-log2(x)` for `x` in `]0;1[`.
+fn neg_log2(x: u128) -> u128 {
+    // Normalize `x = m * 2^-shift` with `m` in `[1;2[`.
+    let shift = x.leading_zeros() - (127 - FRACTION_BITS);
+    let mut m = x << shift;
+
+    // Compute the bits of `log2(m)` one at a time by squaring `m`.
+    let mut log2_m = 0;
+
+    for _ in 0..FRACTION_BITS {
+        m = mul(m, m);
+        log2_m <<= 1;
+
+        if m >= 2 * ONE {
+            m >>= 1;
+            log2_m |= 1;
+        }
+    }
+
+    ((shift as u128) << FRACTION_BITS) - log2_m
+}
+
+/// Compute `2^-y` for a positive `y`.
+fn exp2_neg(y: u128) -> u128 {
+    let integer = y >> FRACTION_BITS;
+
+    if integer >= FRACTION_BITS as u128 {
+        return 0; // lower than the smallest representable number
+    }
+
+    let mut result = ONE;
+
+    for (i, factor) in EXP2_TABLE.iter().enumerate() {
+        if y & (1 << (FRACTION_BITS as usize - 1 - i)) != 0 {
+            result = mul(result, *factor);
+        }
+    }
+
+    result >> integer
+}
+
+/// Compute a "random number" in interval [0;1[.
+///
+/// Uses the same hash as `random`, keeping only its most significant bits.
+pub fn random_fixed(seed: &[u8], height: u64, shard: u64, validator: u64) -> u128 {
+    let mut hasher = Sha3_256::new();
+    hasher.input(seed);
+    hasher.input(shard.to_be_bytes());
+    hasher.input(height.to_be_bytes());
+    hasher.input(validator.to_be_bytes());
+    let hash = hasher.result();
+
+    // Hash is interpreted with the least significant byte first.
+    let mut high = [0; 8];
+    high.copy_from_slice(&hash[24..32]);
+    u64::from_le_bytes(high) as u128
+}
+
+/// Convert a power in interval [0;1] to fixed-point (rounded down).
+pub fn power_to_fixed(power: &Float) -> u128 {
+    if *power >= 1 {
+        return ONE;
+    }
+
+    if *power <= 0 {
+        return 0;
+    }
+
+    let scaled = Float::with_val(power.prec() + FRACTION_BITS, power << FRACTION_BITS).floor();
+    let scaled: Integer = scaled.to_integer().unwrap();
+    scaled.to_u128().unwrap()
+}
+
+/// Weight formula `rand^(1/power)` using only integer operations.
+///
+/// `power` and the returned weight are fixed-point numbers with
+/// `FRACTION_BITS` fractional bits.
+pub fn weight_fixed_raw(seed: &[u8], power: u128, height: u64, shard: u64, validator: u64) -> u128 {
+    let rand = random_fixed(seed, height, shard, validator);
+
+    if rand == 0 || power == 0 {
+        return 0;
+    }
+
+    // rand^(1/power) = 2^(log2(rand) / power)
+    let log = neg_log2(rand);
+
+    let integer = log / power;
+    if integer >= FRACTION_BITS as u128 {
+        return 0; // lower than the smallest representable number
+    }
+
+    let fraction = ((log % power) << FRACTION_BITS) / power;
+
+    exp2_neg((integer << FRACTION_BITS) + fraction)
+}
+
+/// Weight formula using fixed-point numbers.
+///
+/// Has the same signature as other weight formulas, but always computes with
+/// `FRACTION_BITS` fractional bits, from the 64 bits of `random_fixed` :
+/// `precision` only sets the precision of the returned `Float`, which holds
+/// the fixed-point weight exactly, and thus uses at least `FRACTION_BITS` bits.
+pub fn weight_fixed(
+    seed: &[u8],
+    power: &Float,
+    height: u64,
+    shard: u64,
+    validator: u64,
+    precision: u32,
+) -> Float {
+    let weight = weight_fixed_raw(seed, power_to_fixed(power), height, shard, validator);
+
+    Float::with_val(precision.max(FRACTION_BITS), weight) >> FRACTION_BITS
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{weight::weight_exp, FLOAT_PRECISION};
+
+    #[test]
+    fn matches_weight_exp() {
+        for power in &[0.01, 0.1, 0.25, 0.5, 0.9] {
+            let power = Float::with_val(FLOAT_PRECISION, *power);
+
+            for height in 0..200 {
+                let float = weight_exp(b"seed", &power, height, 0, 7, FLOAT_PRECISION);
+                let fixed = weight_fixed(b"seed", &power, height, 0, 7, FLOAT_PRECISION);
+
+                let diff: Float = float - fixed;
+                assert!(diff.abs() < 1e-12, "power {} height {}", power, height);
+            }
+        }
+    }
+
+    #[test]
+    fn full_power_returns_random() {
+        for height in 0..100 {
+            let rand = random_fixed(b"seed", height, 0, 3);
+            let weight = weight_fixed_raw(b"seed", ONE, height, 0, 3);
+
+            assert!(weight.abs_diff(rand) < 1 << 12, "height {}", height);
+        }
+    }
+
+    #[test]
+    fn null_power_returns_zero() {
+        assert_eq!(weight_fixed_raw(b"seed", 0, 1, 0, 3), 0);
+    }
+
+    #[test]
+    fn power_conversion() {
+        let precision = FLOAT_PRECISION;
+
+        assert_eq!(power_to_fixed(&Float::with_val(precision, 0.5)), ONE / 2);
+        assert_eq!(power_to_fixed(&Float::with_val(precision, 2)), ONE);
+        assert_eq!(power_to_fixed(&Float::with_val(precision, -1)), 0);
+    }
+
+    #[test]
+    fn log_and_exp_are_inverse() {
+        for x in &[ONE / 2, ONE / 3, ONE / 1000, ONE - 1] {
+            let y = exp2_neg(neg_log2(*x));
+            assert!(y.abs_diff(*x) < 1 << 12, "{} -> {}", x, y);
+        }
+    }
+}
@@ -0,0 +1,15 @@
+//! Code shared by the Racoon simulations : stake generation, weight formulas,
+//! event queue, chain types, validators behaviours, VDF and shard topologies.
+
+pub mod behaviour;
+pub mod chain;
+pub mod event;
+pub mod fixed;
+pub mod rng;
+pub mod stake;
+pub mod topology;
+pub mod vdf;
+pub mod weight;
+
+/// Default precision in bits of the floating point numbers.
+pub const FLOAT_PRECISION: u32 = 53;
@@ -0,0 +1,65 @@
+//! Deterministic pseudo random numbers derived from hashes, so simulations
+//! can be reproduced.
+
+/// Uniform number in [0; 1) derived from a domain and a list of values.
+pub fn uniform(domain: &[u8], values: &[u64]) -> f64 {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(domain);
+    for value in values {
+        hasher.update(&value.to_be_bytes());
+    }
+    let hash = hasher.finalize();
+
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&hash.as_bytes()[..8]);
+
+    // keep 53 bits to fit the f64 mantissa
+    (u64::from_be_bytes(bytes) >> 11) as f64 / (1u64 << 53) as f64
+}
+
+/// Standard normal number derived from a domain and a list of values, using
+/// the Box-Muller transform.
+pub fn normal(domain: &[u8], values: &[u64]) -> f64 {
+    let mut values = values.to_vec();
+
+    values.push(0);
+    let u1 = 1.0 - uniform(domain, &values); // in (0; 1], finite logarithm
+    *values.last_mut().unwrap() = 1;
+    let u2 = uniform(domain, &values);
+
+    (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uniform_is_deterministic_and_in_range() {
+        for i in 0..1000 {
+            let u = uniform(b"test", &[i, 1]);
+
+            assert!((0.0..1.0).contains(&u));
+            assert_eq!(u, uniform(b"test", &[i, 1]));
+        }
+
+        assert_ne!(uniform(b"test", &[1]), uniform(b"other", &[1]));
+    }
+
+    #[test]
+    fn uniform_mean() {
+        let mean = (0..10_000).map(|i| uniform(b"mean", &[i])).sum::<f64>() / 10_000.0;
+
+        assert!((mean - 0.5).abs() < 0.01);
+    }
+
+    #[test]
+    fn normal_moments() {
+        let draws: Vec<_> = (0..10_000).map(|i| normal(b"normal", &[i])).collect();
+        let mean = draws.iter().sum::<f64>() / draws.len() as f64;
+        let variance = draws.iter().map(|x| (x - mean).powi(2)).sum::<f64>() / draws.len() as f64;
+
+        assert!(mean.abs() < 0.05);
+        assert!((variance - 1.0).abs() < 0.05);
+    }
+}
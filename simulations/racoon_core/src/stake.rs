@@ -0,0 +1,347 @@
+//! Validators stakes generation.
+
+use rug::{float::Constant, integer::Order, ops::Pow, Float, Integer};
+use serde::Deserialize;
+use sha3::{Digest, Sha3_256};
+
+/// Distribution from which validators stakes are drawn.
+#[derive(Clone, Debug, Deserialize)]
+pub enum StakeDistribution {
+    /// `1 + (10 * hash / 2^256)^spread_factor`, see `stake`.
+    Hash { spread_factor: u32 },
+    /// Stakes uniformly distributed between `min` and `max`.
+    Uniform { min: f64, max: f64 },
+    /// Pareto distribution with minimum stake `scale` and tail index `shape`.
+    /// Lower shape will result in a heavier tail.
+    Pareto { scale: f64, shape: f64 },
+    /// Zipf's law : the validator of rank `k` has a stake proportional to
+    /// `1 / k^exponent`.
+    Zipf { exponent: f64 },
+    /// Log-normal distribution : the logarithm of stakes follows a normal
+    /// distribution of mean `mu` and standard deviation `sigma`.
+    LogNormal { mu: f64, sigma: f64 },
+    /// One validator owns `share` of the total stake, the others split the rest
+    /// equally.
+    Whale { share: f64 },
+    /// Real stakes loaded from a CSV file, one validator per line with its
+    /// stake in the last column. A non numeric first line is treated as a
+    /// header. Stakes must be positive, the first ones are used when the file
+    /// has more lines than validators.
+    Csv { path: String },
+}
+
+impl StakeDistribution {
+    /// Generate the stakes of `count` validators, in validator id order.
+    pub fn stakes(&self, count: usize, precision: u32) -> Vec<Float> {
+        match self {
+            StakeDistribution::Hash { spread_factor } => (0..count)
+                .map(|i| stake(i as u64, *spread_factor, precision))
+                .collect(),
+            StakeDistribution::Uniform { min, max } => {
+                assert!(
+                    *min > 0.0 && min <= max,
+                    "uniform stakes must satisfy 0 < min <= max, got min {} and max {}",
+                    min,
+                    max
+                );
+
+                (0..count)
+                    .map(|i| {
+                        let u = uniform(i as u64, 0, precision);
+                        u * (max - min) + min
+                    })
+                    .collect()
+            }
+            StakeDistribution::Pareto { scale, shape } => {
+                assert!(
+                    *scale > 0.0 && *shape > 0.0,
+                    "pareto scale and shape must be positive, got scale {} and shape {}",
+                    scale,
+                    shape
+                );
+
+                (0..count)
+                    .map(|i| {
+                        // inverse transform sampling : scale * (1 - u)^(-1 / shape)
+                        let u = uniform(i as u64, 0, precision);
+                        let tail: Float = 1 - u;
+                        tail.pow(-1.0 / shape) * scale
+                    })
+                    .collect()
+            }
+            StakeDistribution::Zipf { exponent } => (0..count)
+                .map(|i| {
+                    let rank = Float::with_val(precision, i + 1);
+                    1 / rank.pow(exponent)
+                })
+                .collect(),
+            StakeDistribution::LogNormal { mu, sigma } => (0..count)
+                .map(|i| {
+                    let normal = normal(i as u64, precision);
+                    let exponent: Float = normal * sigma + mu;
+                    exponent.exp()
+                })
+                .collect(),
+            StakeDistribution::Whale { share } => {
+                assert!(
+                    *share > 0.0 && *share < 1.0,
+                    "whale share must be in ]0;1[, got {}",
+                    share
+                );
+
+                // a single validator owns everything, whatever the share
+                if count <= 1 {
+                    return vec![Float::with_val(precision, 1); count];
+                }
+
+                // minnows have a stake of 1, the whale owns `share` of the total
+                let minnows = count - 1;
+                let whale = Float::with_val(precision, minnows) * share / (1.0 - share);
+
+                let mut stakes = vec![Float::with_val(precision, 1); count];
+                stakes[0] = whale;
+                stakes
+            }
+            StakeDistribution::Csv { path } => {
+                let stakes = stakes_from_csv(path, precision);
+                assert!(
+                    stakes.len() >= count,
+                    "{} contains {} stakes but {} validators are needed",
+                    path,
+                    stakes.len(),
+                    count
+                );
+                stakes.into_iter().take(count).collect()
+            }
+        }
+    }
+}
+
+/// Generate the stake of a validator.
+/// Higher spread factor will result in greater differencies between biggest
+/// validators and the others.
+pub fn stake(id: u64, spread_factor: u32, precision: u32) -> Float {
+    let mut hasher = Sha3_256::new();
+    hasher.input(b"validator");
+    hasher.input(id.to_be_bytes());
+    let stake = hasher.result();
+
+    let stake = Integer::from_digits(&stake, Order::Lsf);
+    let stake = Float::with_val(precision, stake);
+
+    let hash_max = Float::with_val(precision, 2).pow(256);
+
+    let stake: Float = stake / hash_max;
+    let stake: Float = stake * 10;
+    let stake = stake.pow(spread_factor);
+
+    1 + stake
+}
+
+/// Deterministic uniform number in [0; 1) for a validator.
+/// `draw` allows to get multiple independent numbers for the same validator.
+fn uniform(id: u64, draw: u64, precision: u32) -> Float {
+    let mut hasher = Sha3_256::new();
+    hasher.input(b"stake");
+    hasher.input(id.to_be_bytes());
+    hasher.input(draw.to_be_bytes());
+    let hash = hasher.result();
+
+    let hash = Integer::from_digits(&hash, Order::Lsf);
+    let hash = Float::with_val(precision, hash);
+
+    let hash_max = Float::with_val(precision, 2).pow(256);
+
+    hash / hash_max
+}
+
+/// Deterministic standard normal number for a validator, using the Box-Muller
+/// transform.
+fn normal(id: u64, precision: u32) -> Float {
+    // 1 - u is in (0; 1], so its logarithm is finite
+    let u1: Float = 1 - uniform(id, 0, precision);
+    let u2 = uniform(id, 1, precision);
+
+    let radius = (u1.ln() * -2i32).sqrt();
+    let angle = Float::with_val(precision, Constant::Pi) * 2u32 * u2;
+
+    radius * angle.cos()
+}
+
+/// Read stakes from the last column of a CSV file.
+fn stakes_from_csv(path: &str, precision: u32) -> Vec<Float> {
+    let content =
+        std::fs::read_to_string(path).unwrap_or_else(|e| panic!("cannot read {}: {}", path, e));
+
+    content
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .filter_map(|(i, line)| {
+            let field = line.rsplit(',').next().unwrap().trim();
+
+            match field.parse::<f64>() {
+                Ok(stake) if stake > 0.0 && stake.is_finite() => {
+                    Some(Float::with_val(precision, stake))
+                }
+                Ok(stake) => panic!("{}:{} stake must be positive, got {}", path, i + 1, stake),
+                Err(_) if i == 0 => None, // header
+                Err(e) => panic!("{}:{} invalid stake {:?} : {}", path, i + 1, field, e),
+            }
+        })
+        .collect()
+}
+
+/// Generate validators powers, highest first.
+/// They sum up to 1.
+pub fn powers(distribution: &StakeDistribution, count: usize, precision: u32) -> Vec<Float> {
+    let mut stakes = distribution.stakes(count, precision);
+    let mut stakes_sum = Float::with_val(precision, 0);
+
+    // compute stakes sum
+    for stake in &stakes {
+        stakes_sum += stake;
+    }
+
+    stakes.sort_by(|a, b| b.partial_cmp(a).unwrap()); // highest first
+    stakes
+        .into_iter()
+        .map(|s| Float::with_val(precision, s) / stakes_sum.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FLOAT_PRECISION;
+
+    fn distributions() -> Vec<StakeDistribution> {
+        vec![
+            StakeDistribution::Hash { spread_factor: 5 },
+            StakeDistribution::Uniform {
+                min: 1.0,
+                max: 10.0,
+            },
+            StakeDistribution::Pareto {
+                scale: 1.0,
+                shape: 1.5,
+            },
+            StakeDistribution::Zipf { exponent: 1.0 },
+            StakeDistribution::LogNormal {
+                mu: 0.0,
+                sigma: 1.0,
+            },
+            StakeDistribution::Whale { share: 0.4 },
+        ]
+    }
+
+    #[test]
+    fn powers_sum_to_one() {
+        for distribution in distributions() {
+            let powers = powers(&distribution, 20, FLOAT_PRECISION);
+            let sum: Float = Float::with_val(FLOAT_PRECISION, Float::sum(powers.iter()));
+
+            assert_eq!(powers.len(), 20);
+            assert!((sum - 1.0f64).abs() < 1e-12, "{:?}", distribution);
+        }
+    }
+
+    #[test]
+    fn powers_are_sorted_and_positive() {
+        for distribution in distributions() {
+            let powers = powers(&distribution, 20, FLOAT_PRECISION);
+
+            assert!(
+                powers.windows(2).all(|w| w[0] >= w[1]),
+                "{:?}",
+                distribution
+            );
+            assert!(*powers.last().unwrap() > 0, "{:?}", distribution);
+        }
+    }
+
+    #[test]
+    fn stakes_are_deterministic() {
+        for distribution in distributions() {
+            assert_eq!(
+                distribution.stakes(10, FLOAT_PRECISION),
+                distribution.stakes(10, FLOAT_PRECISION)
+            );
+        }
+    }
+
+    #[test]
+    fn whale_owns_its_share() {
+        let powers = powers(
+            &StakeDistribution::Whale { share: 0.4 },
+            20,
+            FLOAT_PRECISION,
+        );
+
+        assert!((powers[0].to_f64() - 0.4).abs() < 1e-12);
+        assert!((powers[1].to_f64() - 0.6 / 19.0).abs() < 1e-12);
+    }
+
+    #[test]
+    fn single_whale_owns_everything() {
+        let powers = powers(&StakeDistribution::Whale { share: 0.4 }, 1, FLOAT_PRECISION);
+
+        assert_eq!(powers, vec![Float::with_val(FLOAT_PRECISION, 1)]);
+    }
+
+    #[test]
+    #[should_panic(expected = "whale share")]
+    fn whale_share_must_be_below_one() {
+        StakeDistribution::Whale { share: 1.0 }.stakes(10, FLOAT_PRECISION);
+    }
+
+    #[test]
+    #[should_panic(expected = "uniform stakes")]
+    fn uniform_min_must_be_below_max() {
+        StakeDistribution::Uniform { min: 3.0, max: 2.0 }.stakes(10, FLOAT_PRECISION);
+    }
+
+    #[test]
+    #[should_panic(expected = "uniform stakes")]
+    fn uniform_min_must_be_positive() {
+        StakeDistribution::Uniform { min: 0.0, max: 2.0 }.stakes(10, FLOAT_PRECISION);
+    }
+
+    #[test]
+    #[should_panic(expected = "pareto scale and shape")]
+    fn pareto_shape_must_be_positive() {
+        StakeDistribution::Pareto {
+            scale: 1.0,
+            shape: 0.0,
+        }
+        .stakes(10, FLOAT_PRECISION);
+    }
+
+    #[test]
+    #[should_panic(expected = "stake must be positive")]
+    fn csv_stakes_must_be_positive() {
+        let path = std::env::temp_dir().join("racoon_null_stake.csv");
+        std::fs::write(&path, "validator,stake\n0,10\n1,0\n").unwrap();
+
+        StakeDistribution::Csv {
+            path: path.to_str().unwrap().to_string(),
+        }
+        .stakes(2, FLOAT_PRECISION);
+    }
+
+    #[test]
+    #[should_panic(expected = "cannot read")]
+    fn missing_csv_names_the_file() {
+        StakeDistribution::Csv {
+            path: "missing_stakes.csv".to_string(),
+        }
+        .stakes(10, FLOAT_PRECISION);
+    }
+
+    #[test]
+    fn uniform_stakes_are_in_range() {
+        let stakes = StakeDistribution::Uniform { min: 2.0, max: 3.0 }.stakes(100, FLOAT_PRECISION);
+
+        assert!(stakes.iter().all(|s| *s >= 2.0 && *s < 3.0));
+    }
+}
@@ -0,0 +1,300 @@
+//! Shard topologies : which shards include the finalized headers of each
+//! other, and thus the paths followed by cross-shard messages.
+
+use crate::rng;
+use serde::Deserialize;
+use std::collections::{BTreeSet, VecDeque};
+
+/// Shape of the graph linking shards.
+#[derive(Clone, Debug, Deserialize)]
+pub enum Topology {
+    /// Shard `i` is the parent of shards `i * arity + 1` to `i * arity + arity`,
+    /// shard 0 being the root.
+    Tree { arity: usize },
+    /// Shards laid row by row on a square grid, linked to their 4 neighbours.
+    Grid,
+    /// Uniformly drawn graph where every shard has `degree` neighbours.
+    RandomRegular { degree: usize },
+    /// Watts-Strogatz graph : ring where each shard is linked to its `degree`
+    /// nearest shards, each link being moved to a random shard with
+    /// probability `rewiring`.
+    SmallWorld { degree: usize, rewiring: f64 },
+}
+
+/// Undirected graph between shards.
+#[derive(Clone, Debug)]
+pub struct Graph {
+    /// Neighbours of each shard.
+    neighbours: Vec<BTreeSet<usize>>,
+}
+
+impl Topology {
+    /// Build the graph linking given amount of shards.
+    pub fn graph(&self, shards: usize) -> Graph {
+        match self {
+            Topology::Tree { arity } => tree(shards, *arity),
+            Topology::Grid => grid(shards),
+            Topology::RandomRegular { degree } => random_regular(shards, *degree),
+            Topology::SmallWorld { degree, rewiring } => small_world(shards, *degree, *rewiring),
+        }
+    }
+}
+
+impl Graph {
+    fn new(shards: usize) -> Self {
+        Self {
+            neighbours: vec![BTreeSet::new(); shards],
+        }
+    }
+
+    /// Link 2 shards, returning false if they are the same or already linked.
+    fn link(&mut self, a: usize, b: usize) -> bool {
+        if a == b || self.neighbours[a].contains(&b) {
+            return false;
+        }
+
+        self.neighbours[a].insert(b);
+        self.neighbours[b].insert(a);
+        true
+    }
+
+    fn unlink(&mut self, a: usize, b: usize) {
+        self.neighbours[a].remove(&b);
+        self.neighbours[b].remove(&a);
+    }
+
+    /// Amount of shards.
+    pub fn shards(&self) -> usize {
+        self.neighbours.len()
+    }
+
+    /// Shards linked to given shard.
+    pub fn neighbours(&self, shard: usize) -> &BTreeSet<usize> {
+        &self.neighbours[shard]
+    }
+
+    /// Amount of links.
+    pub fn links(&self) -> usize {
+        self.neighbours.iter().map(BTreeSet::len).sum::<usize>() / 2
+    }
+
+    /// Hop count of the shortest path from given shard to every shard, none
+    /// if a shard can't be reached.
+    pub fn hops_from(&self, source: usize) -> Vec<Option<u32>> {
+        let mut hops = vec![None; self.shards()];
+        let mut queue = VecDeque::new();
+
+        hops[source] = Some(0);
+        queue.push_back(source);
+
+        while let Some(shard) = queue.pop_front() {
+            let next = hops[shard].unwrap() + 1;
+
+            for &neighbour in &self.neighbours[shard] {
+                if hops[neighbour].is_none() {
+                    hops[neighbour] = Some(next);
+                    queue.push_back(neighbour);
+                }
+            }
+        }
+
+        hops
+    }
+}
+
+fn tree(shards: usize, arity: usize) -> Graph {
+    assert!(arity > 0, "tree arity must be positive");
+
+    let mut graph = Graph::new(shards);
+
+    for shard in 1..shards {
+        graph.link(shard, (shard - 1) / arity);
+    }
+
+    graph
+}
+
+fn grid(shards: usize) -> Graph {
+    let width = (shards as f64).sqrt().ceil() as usize;
+    let mut graph = Graph::new(shards);
+
+    for shard in 0..shards {
+        if (shard + 1) % width != 0 && shard + 1 < shards {
+            graph.link(shard, shard + 1);
+        }
+
+        if shard + width < shards {
+            graph.link(shard, shard + width);
+        }
+    }
+
+    graph
+}
+
+/// Pairing model : each shard gets `degree` link ends, which are shuffled and
+/// paired. Pairings with loops or duplicated links are drawn again, so the
+/// graph is uniform among regular graphs.
+fn random_regular(shards: usize, degree: usize) -> Graph {
+    assert!(
+        degree < shards,
+        "degree must be lower than the shards count"
+    );
+    assert!(
+        (shards * degree).is_multiple_of(2),
+        "shards count times degree must be even"
+    );
+
+    for attempt in 0.. {
+        let mut ends: Vec<usize> = (0..shards * degree).map(|end| end / degree).collect();
+        shuffle(b"random regular", &mut ends, &[shards as u64, attempt]);
+
+        let mut graph = Graph::new(shards);
+
+        if ends.chunks(2).all(|pair| graph.link(pair[0], pair[1])) {
+            return graph;
+        }
+    }
+
+    unreachable!()
+}
+
+fn small_world(shards: usize, degree: usize, rewiring: f64) -> Graph {
+    assert!(degree.is_multiple_of(2), "small world degree must be even");
+    assert!(
+        degree < shards,
+        "degree must be lower than the shards count"
+    );
+
+    let mut graph = Graph::new(shards);
+
+    for shard in 0..shards {
+        for distance in 1..=degree / 2 {
+            graph.link(shard, (shard + distance) % shards);
+        }
+    }
+
+    for shard in 0..shards {
+        for distance in 1..=degree / 2 {
+            let neighbour = (shard + distance) % shards;
+            let values = [shards as u64, shard as u64, distance as u64];
+
+            if rng::uniform(b"rewire", &values) >= rewiring {
+                continue;
+            }
+
+            // keep the link if the shard is already linked to every other one
+            if graph.neighbours(shard).len() + 1 >= shards {
+                continue;
+            }
+
+            for draw in 0.. {
+                let values = [shards as u64, shard as u64, distance as u64, draw];
+                let target = (rng::uniform(b"rewire target", &values) * shards as f64) as usize;
+
+                if graph.link(shard, target) {
+                    graph.unlink(shard, neighbour);
+                    break;
+                }
+            }
+        }
+    }
+
+    graph
+}
+
+/// Fisher-Yates shuffle with deterministic random numbers.
+fn shuffle(domain: &[u8], items: &mut [usize], values: &[u64]) {
+    let mut values = values.to_vec();
+    values.push(0);
+
+    for i in (1..items.len()).rev() {
+        *values.last_mut().unwrap() = i as u64;
+        let j = (rng::uniform(domain, &values) * (i + 1) as f64) as usize;
+        items.swap(i, j);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn connected(graph: &Graph) -> bool {
+        graph.hops_from(0).iter().all(Option::is_some)
+    }
+
+    #[test]
+    fn tree_links_shards_to_their_parent() {
+        for arity in 1..5 {
+            let graph = Topology::Tree { arity }.graph(50);
+
+            assert_eq!(graph.links(), 49);
+            for shard in 1..50 {
+                assert!(graph.neighbours(shard).contains(&((shard - 1) / arity)));
+            }
+            assert!(connected(&graph));
+        }
+    }
+
+    #[test]
+    fn tree_root_is_linked_to_its_children() {
+        let graph = Topology::Tree { arity: 3 }.graph(20);
+
+        assert_eq!(graph.neighbours(0), &[1, 2, 3].iter().copied().collect());
+        assert_eq!(graph.neighbours(1), &[0, 4, 5, 6].iter().copied().collect());
+    }
+
+    #[test]
+    fn grid_links_4_neighbours_at_most() {
+        let graph = Topology::Grid.graph(16);
+
+        // 4x4 grid : corners, borders and inner shards
+        assert_eq!(graph.neighbours(0).len(), 2);
+        assert_eq!(graph.neighbours(1).len(), 3);
+        assert_eq!(graph.neighbours(5).len(), 4);
+        assert_eq!(graph.neighbours(5), &[1, 4, 6, 9].iter().copied().collect());
+        assert_eq!(graph.links(), 24);
+        assert!(connected(&graph));
+    }
+
+    #[test]
+    fn incomplete_grid_is_connected() {
+        for shards in 1..30 {
+            let graph = Topology::Grid.graph(shards);
+
+            assert!((0..shards).all(|shard| graph.neighbours(shard).len() <= 4));
+            assert!(connected(&graph), "{} shards", shards);
+        }
+    }
+
+    #[test]
+    fn random_regular_has_given_degree() {
+        for &(shards, degree) in &[(10, 3), (16, 4), (64, 4), (100, 5)] {
+            let graph = Topology::RandomRegular { degree }.graph(shards);
+
+            for shard in 0..shards {
+                assert_eq!(graph.neighbours(shard).len(), degree, "shard {}", shard);
+                assert!(!graph.neighbours(shard).contains(&shard));
+            }
+            assert_eq!(graph.links(), shards * degree / 2);
+            assert!(connected(&graph), "{} shards", shards);
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "must be even")]
+    fn random_regular_needs_even_link_ends() {
+        Topology::RandomRegular { degree: 3 }.graph(11);
+    }
+
+    #[test]
+    fn small_world_keeps_its_links() {
+        let graph = Topology::SmallWorld {
+            degree: 4,
+            rewiring: 0.1,
+        }
+        .graph(64);
+
+        assert_eq!(graph.links(), 128);
+        assert!(connected(&graph));
+    }
+}
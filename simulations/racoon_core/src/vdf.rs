@@ -0,0 +1,229 @@
+//! Wesolowski verifiable delay function over an RSA group.
+//!
+//! The output of `eval` is `g^(2^iterations) mod N` with `g` derived from the
+//! input. The proof is `g^floor(2^iterations / l)` where `l` is a prime
+//! derived from `g` and the output, which can be checked with only 2 modular
+//! exponentiations.
+
+use rug::{integer::Order, Integer};
+use sha3::{Digest, Sha3_256};
+
+/// Size in bits of the prime used by the proof.
+const CHALLENGE_BITS: u32 = 128;
+
+/// Proof that a VDF output has been computed with given iterations.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Proof {
+    /// Number of sequential squarings.
+    pub iterations: u64,
+    /// Wesolowski proof `g^floor(2^iterations / l)`.
+    pub pi: Integer,
+}
+
+/// VDF over the RSA group of given modulus.
+#[derive(Clone, Debug)]
+pub struct Vdf {
+    modulus: Integer,
+}
+
+impl Vdf {
+    /// Create a VDF with a modulus of unknown factorization.
+    pub fn new(modulus: Integer) -> Self {
+        Self { modulus }
+    }
+
+    /// Create a VDF with a modulus derived from a seed.
+    /// Anyone knowing the seed can compute the factorization and shortcut the
+    /// VDF, so it must only be used in simulations.
+    pub fn from_seed(seed: &[u8], bits: u32) -> Self {
+        let p = hash_to_prime(&[b"vdf modulus p", seed], bits / 2);
+        let q = hash_to_prime(&[b"vdf modulus q", seed], bits - bits / 2);
+
+        Self::new(p * q)
+    }
+
+    /// RSA modulus of the group.
+    pub fn modulus(&self) -> &Integer {
+        &self.modulus
+    }
+
+    /// Evaluate the VDF on given input, returning the output and its proof.
+    pub fn eval(&self, input: &[u8], iterations: u64) -> (Integer, Proof) {
+        let g = self.hash_to_group(input);
+
+        let mut output = g.clone();
+        for _ in 0..iterations {
+            output.square_mut();
+            output %= &self.modulus;
+        }
+
+        let l = self.challenge(&g, &output);
+
+        // long division of 2^iterations by l, one bit at a time
+        let mut pi = Integer::from(1);
+        let mut remainder = Integer::from(1);
+        for _ in 0..iterations {
+            remainder <<= 1;
+            pi.square_mut();
+
+            if remainder >= l {
+                remainder -= &l;
+                pi *= &g;
+            }
+
+            pi %= &self.modulus;
+        }
+
+        (output, Proof { iterations, pi })
+    }
+
+    /// Verify that `output` is the result of the VDF on `input`.
+    pub fn verify(&self, input: &[u8], output: &Integer, proof: &Proof) -> bool {
+        if *output <= 0 || *output >= self.modulus || proof.pi <= 0 || proof.pi >= self.modulus {
+            return false;
+        }
+
+        let g = self.hash_to_group(input);
+        let l = self.challenge(&g, output);
+
+        // 2^iterations mod l
+        let r = Integer::from(2)
+            .pow_mod(&Integer::from(proof.iterations), &l)
+            .unwrap();
+
+        let pi_l = proof.pi.clone().pow_mod(&l, &self.modulus).unwrap();
+        let g_r = g.pow_mod(&r, &self.modulus).unwrap();
+
+        (pi_l * g_r) % &self.modulus == *output
+    }
+
+    /// Map an input to an element of the group.
+    fn hash_to_group(&self, input: &[u8]) -> Integer {
+        let bits = self.modulus.significant_bits() + CHALLENGE_BITS;
+        hash_to_integer(&[b"vdf input", input], bits) % &self.modulus
+    }
+
+    /// Prime challenge of the proof, derived from the input element and the
+    /// output.
+    fn challenge(&self, g: &Integer, output: &Integer) -> Integer {
+        let g = g.to_digits::<u8>(Order::Msf);
+        let output = output.to_digits::<u8>(Order::Msf);
+
+        hash_to_prime(&[b"vdf challenge", &g, &output], CHALLENGE_BITS)
+    }
+}
+
+/// Expand the hash of given parts into an integer of `bits` bits.
+fn hash_to_integer(parts: &[&[u8]], bits: u32) -> Integer {
+    let bytes = (bits as usize).div_ceil(8);
+    let mut digits = Vec::with_capacity(bytes + 32);
+    let mut counter = 0u64;
+
+    while digits.len() < bytes {
+        let mut hasher = Sha3_256::new();
+        hasher.input(counter.to_be_bytes());
+        for part in parts {
+            hasher.input((part.len() as u64).to_be_bytes());
+            hasher.input(part);
+        }
+        digits.extend_from_slice(&hasher.result());
+        counter += 1;
+    }
+
+    digits.truncate(bytes);
+    Integer::from_digits(&digits, Order::Msf) >> (bytes as u32 * 8 - bits)
+}
+
+/// Smallest prime greater than the hash of given parts, with its 2 top bits
+/// set so it has exactly `bits` bits (with overwhelming probability) and the
+/// product of 2 such primes has exactly the sum of their sizes.
+fn hash_to_prime(parts: &[&[u8]], bits: u32) -> Integer {
+    let mut candidate = hash_to_integer(parts, bits);
+    candidate.set_bit(bits - 1, true);
+    candidate.set_bit(bits - 2, true);
+    candidate.next_prime()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ITERATIONS: u64 = 1000;
+
+    fn vdf() -> Vdf {
+        Vdf::from_seed(b"test", 512)
+    }
+
+    #[test]
+    fn modulus_has_requested_size() {
+        assert_eq!(vdf().modulus().significant_bits(), 512);
+    }
+
+    #[test]
+    fn output_is_repeated_squaring() {
+        let vdf = vdf();
+        let (output, _) = vdf.eval(b"input", ITERATIONS);
+
+        let g = vdf.hash_to_group(b"input");
+        let exponent = Integer::from(1) << ITERATIONS as u32;
+        assert_eq!(output, g.pow_mod(&exponent, vdf.modulus()).unwrap());
+    }
+
+    #[test]
+    fn valid_proof_is_accepted() {
+        let vdf = vdf();
+
+        for iterations in &[0, 1, ITERATIONS] {
+            let (output, proof) = vdf.eval(b"input", *iterations);
+
+            assert_eq!(proof.iterations, *iterations);
+            assert!(vdf.verify(b"input", &output, &proof));
+        }
+    }
+
+    #[test]
+    fn tampered_output_is_rejected() {
+        let vdf = vdf();
+        let (output, proof) = vdf.eval(b"input", ITERATIONS);
+
+        assert!(!vdf.verify(b"input", &(output.clone() + 1), &proof));
+        assert!(!vdf.verify(b"input", &Integer::from(0), &proof));
+        assert!(!vdf.verify(b"input", &(output + vdf.modulus()), &proof));
+    }
+
+    #[test]
+    fn tampered_proof_is_rejected() {
+        let vdf = vdf();
+        let (output, mut proof) = vdf.eval(b"input", ITERATIONS);
+        proof.pi += 1;
+
+        assert!(!vdf.verify(b"input", &output, &proof));
+    }
+
+    #[test]
+    fn wrong_iterations_are_rejected() {
+        let vdf = vdf();
+        let (output, mut proof) = vdf.eval(b"input", ITERATIONS);
+
+        proof.iterations += 1;
+        assert!(!vdf.verify(b"input", &output, &proof));
+
+        proof.iterations -= 2;
+        assert!(!vdf.verify(b"input", &output, &proof));
+    }
+
+    #[test]
+    fn wrong_input_is_rejected() {
+        let vdf = vdf();
+        let (output, proof) = vdf.eval(b"input", ITERATIONS);
+
+        assert!(!vdf.verify(b"other input", &output, &proof));
+    }
+
+    #[test]
+    fn other_modulus_rejects_proof() {
+        let (output, proof) = vdf().eval(b"input", ITERATIONS);
+
+        assert!(!Vdf::from_seed(b"other", 512).verify(b"input", &output, &proof));
+    }
+}
@@ -0,0 +1,154 @@
+//! Blocks weight formulas.
+
+use rug::{integer::Order, ops::Pow, Float, Integer};
+use sha3::{Digest, Sha3_256};
+
+/// Compute a "random number".
+pub fn random(seed: &[u8], height: u64, shard: u64, validator: u64, precision: u32) -> Float {
+    // Generate "random" number.
+    let mut hasher = Sha3_256::new();
+    hasher.input(seed);
+    hasher.input(shard.to_be_bytes());
+    hasher.input(height.to_be_bytes());
+    hasher.input(validator.to_be_bytes());
+    let hash = hasher.result();
+
+    let hash = Integer::from_digits(&hash, Order::Lsf);
+    Float::with_val(precision, hash)
+}
+
+/// Weight forumula using a single exp.
+pub fn weight_exp(
+    seed: &[u8],
+    power: &Float,
+    height: u64,
+    shard: u64,
+    validator: u64,
+    precision: u32,
+) -> Float {
+    let rand = random(seed, height, shard, validator, precision);
+
+    // Transform number in interval [0;1].
+    let hash_max = Float::with_val(precision, 2).pow(256);
+    let rand: Float = rand / hash_max;
+
+    // Compute weight.
+    rand.pow(Float::with_val(precision, 1 / power))
+}
+
+pub fn weight_log(
+    seed: &[u8],
+    power: &Float,
+    height: u64,
+    shard: u64,
+    validator: u64,
+    precision: u32,
+) -> Float {
+    let rand = random(seed, height, shard, validator, precision);
+
+    // Compute weight.
+    let ln_r = rand.ln();
+    let hash_max: Float = Float::with_val(precision, 2).pow(256);
+    let ln_max = hash_max.clone().ln();
+    let ln_d = Float::with_val(precision, 5).ln();
+
+    (ln_r - ln_max) / (power * ln_d) // + hash_max
+}
+
+/// Compute a "random number" for a block using blake3.
+pub fn block_random(
+    epoch_seed: &[u8],
+    shard_id: u64,
+    block_height: u64,
+    validator_id: u64,
+    precision: u32,
+) -> Float {
+    let mut hasher = blake3::Hasher::new();
+    hasher.update(epoch_seed);
+    hasher.update(&shard_id.to_be_bytes());
+    hasher.update(&block_height.to_be_bytes());
+    hasher.update(&validator_id.to_be_bytes());
+    let hash = hasher.finalize();
+    let hash = Integer::from_digits(hash.as_bytes(), Order::Lsf);
+
+    Float::with_val(precision, hash)
+}
+
+/// Weight of a block in interval [0;1], using a single exp over `block_random`.
+pub fn block_weight(
+    epoch_seed: &[u8],
+    shard_id: u64,
+    block_height: u64,
+    validator_id: usize,
+    validator_power: &Float,
+    precision: u32,
+) -> Float {
+    let rand = block_random(
+        epoch_seed,
+        shard_id,
+        block_height,
+        validator_id as u64,
+        precision,
+    );
+
+    let max = Float::with_val(precision, 2).pow(256);
+    let rand: Float = rand / max;
+
+    rand.pow(Float::with_val(precision, 1 / validator_power))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::FLOAT_PRECISION;
+
+    #[test]
+    fn weights_are_in_unit_interval() {
+        let power = Float::with_val(FLOAT_PRECISION, 0.1);
+
+        for height in 0..100 {
+            let exp = weight_exp(b"seed", &power, height, 0, 1, FLOAT_PRECISION);
+            let block = block_weight(b"seed", 0, height, 1, &power, FLOAT_PRECISION);
+
+            assert!((0..=1).contains(&exp));
+            assert!((0..=1).contains(&block));
+        }
+    }
+
+    #[test]
+    fn log_is_scaled_log_of_exp() {
+        // weight_log = ln(weight_exp) / ln(5)
+        let power = Float::with_val(FLOAT_PRECISION, 0.3);
+
+        for height in 0..100 {
+            let exp = weight_exp(b"seed", &power, height, 2, 3, FLOAT_PRECISION);
+            let log = weight_log(b"seed", &power, height, 2, 3, FLOAT_PRECISION);
+
+            let expected = exp.ln().to_f64() / 5f64.ln();
+            assert!((log.to_f64() - expected).abs() < 1e-9 * expected.abs().max(1.0));
+        }
+    }
+
+    #[test]
+    fn higher_power_gives_higher_weight() {
+        let low = Float::with_val(FLOAT_PRECISION, 0.1);
+        let high = Float::with_val(FLOAT_PRECISION, 0.5);
+
+        for height in 0..100 {
+            assert!(
+                weight_exp(b"seed", &high, height, 0, 1, FLOAT_PRECISION)
+                    >= weight_exp(b"seed", &low, height, 0, 1, FLOAT_PRECISION)
+            );
+        }
+    }
+
+    #[test]
+    fn random_depends_on_every_input() {
+        let base = random(b"seed", 1, 2, 3, FLOAT_PRECISION);
+
+        assert_ne!(base, random(b"other", 1, 2, 3, FLOAT_PRECISION));
+        assert_ne!(base, random(b"seed", 4, 2, 3, FLOAT_PRECISION));
+        assert_ne!(base, random(b"seed", 1, 4, 3, FLOAT_PRECISION));
+        assert_ne!(base, random(b"seed", 1, 2, 4, FLOAT_PRECISION));
+    }
+}
@@ -0,0 +1,71 @@
+// Cargo run --release --bin mmr_proof_size > mmr_proof_size.csv
+//
+// Append events to a MMR and measure the size of inclusion proofs as the
+// amount of events grows. Sizes right before a power of 2 (many peaks) and
+// right at it (a single peak) bound the other ones.
+//
+// Outputs the proof sizes for each amount of events as CSV.
+
+use racoon_merkle::{hasher::Blake3, mmr::Mmr};
+use serde::Serialize;
+
+/// Highest amount of events is `2^MAX_BITS`.
+const MAX_BITS: u32 = 20;
+/// Amount of leaves whose proof is measured, evenly spread from the oldest
+/// to the newest.
+const SAMPLES: u64 = 4096;
+
+/// Line of the CSV output.
+#[derive(Serialize)]
+struct Line {
+    events: u64,
+    peaks: usize,
+    mean_hashes: f64,
+    max_hashes: usize,
+    oldest_bytes: usize,
+    newest_bytes: usize,
+    mean_bytes: f64,
+    max_bytes: usize,
+}
+
+fn main() {
+    let mut checkpoints: Vec<u64> = (1..=MAX_BITS)
+        .flat_map(|bits| vec![(1 << bits) - 1, 1 << bits])
+        .collect();
+    checkpoints.dedup();
+
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    let mut mmr = Mmr::<Blake3>::new();
+
+    for events in checkpoints {
+        while mmr.len() < events {
+            let event = mmr.len();
+            mmr.append(&event.to_be_bytes());
+        }
+
+        let step = std::cmp::max(1, events / SAMPLES);
+        let mut indexes: Vec<u64> = (0..events).step_by(step as usize).collect();
+        if *indexes.last().unwrap() != events - 1 {
+            indexes.push(events - 1);
+        }
+
+        let proofs: Vec<_> = indexes.iter().map(|&i| mmr.proof(i).unwrap()).collect();
+
+        writer
+            .serialize(Line {
+                events,
+                peaks: mmr.peaks().len(),
+                mean_hashes: proofs.iter().map(|p| p.hashes()).sum::<usize>() as f64
+                    / proofs.len() as f64,
+                max_hashes: proofs.iter().map(|p| p.hashes()).max().unwrap(),
+                oldest_bytes: proofs[0].size(),
+                newest_bytes: proofs.last().unwrap().size(),
+                mean_bytes: proofs.iter().map(|p| p.size()).sum::<usize>() as f64
+                    / proofs.len() as f64,
+                max_bytes: proofs.iter().map(|p| p.size()).max().unwrap(),
+            })
+            .unwrap();
+    }
+
+    writer.flush().unwrap();
+}
@@ -0,0 +1,70 @@
+// Cargo run --release --bin proof_by_age > proof_by_age.csv
+//
+// Compare the inclusion proofs of a CMT and a MMR holding the same events, as
+// a function of the age of the proven event (0 for the newest). The CMT proof
+// grows linearly with the age while the MMR proof depends on the peak holding
+// the event, so recent events are cheaper to prove in a CMT only up to some
+// age.
+//
+// Outputs the proof sizes for each amount of events and age as CSV.
+
+use racoon_merkle::{cmt::Cmt, hasher::Blake3, mmr::Mmr};
+use serde::Serialize;
+
+/// Amounts of events of the compared structures.
+const EVENTS: [u64; 4] = [1 << 10, (1 << 10) + 255, 1 << 16, 1_000_000];
+
+/// Line of the CSV output.
+#[derive(Serialize)]
+struct Line {
+    events: u64,
+    age: u64,
+    cmt_hashes: usize,
+    mmr_hashes: usize,
+    cmt_bytes: usize,
+    mmr_bytes: usize,
+    /// True if the CMT proof is strictly shorter than the MMR one.
+    cmt_shorter: bool,
+}
+
+fn main() {
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    let mut cmt = Cmt::<Blake3>::new();
+    let mut mmr = Mmr::<Blake3>::new();
+
+    for &events in EVENTS.iter() {
+        while cmt.len() < events {
+            let event = cmt.len().to_be_bytes();
+            cmt.append(&event);
+            mmr.append(&event);
+        }
+
+        // Every age while the CMT is competitive, then powers of 2.
+        let mut ages: Vec<u64> = (0..64)
+            .chain((6..64).map(|bits| 1 << bits))
+            .filter(|&age| age < events)
+            .collect();
+        ages.push(events - 1);
+        ages.dedup();
+
+        for age in ages {
+            let index = events - 1 - age;
+            let cmt_proof = cmt.proof(index).unwrap();
+            let mmr_proof = mmr.proof(index).unwrap();
+
+            writer
+                .serialize(Line {
+                    events,
+                    age,
+                    cmt_hashes: cmt_proof.hashes(),
+                    mmr_hashes: mmr_proof.hashes(),
+                    cmt_bytes: cmt_proof.size(),
+                    mmr_bytes: mmr_proof.size(),
+                    cmt_shorter: cmt_proof.hashes() < mmr_proof.hashes(),
+                })
+                .unwrap();
+        }
+    }
+
+    writer.flush().unwrap();
+}
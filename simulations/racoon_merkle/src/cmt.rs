@@ -0,0 +1,139 @@
+//! Comb Merkle Tree ("arbre en râteau") : each leaf is hashed with the root of
+//! all the previous ones, `acc(i) = node(acc(i - 1), leaf(i))`, so the tree is
+//! a comb whose teeth are the leaves.
+//!
+//! The proof of an event holds the accumulator before it and every newer leaf,
+//! so it grows linearly with the event age : recent events have the shortest
+//! proofs, unlike a MMR where proofs grow with the log of the amount of events.
+
+use crate::{hasher::Hasher, Hash};
+use std::marker::PhantomData;
+
+/// Comb Merkle Tree of events hashed with `H`.
+#[derive(Debug, Clone)]
+pub struct Cmt<H: Hasher> {
+    /// Hashes of the leaves.
+    leaves: Vec<Hash>,
+    /// Accumulator after each leaf.
+    accumulators: Vec<Hash>,
+    hasher: PhantomData<H>,
+}
+
+/// Proof that a leaf is included in a CMT of given size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proof {
+    /// Index of the leaf.
+    pub leaf_index: u64,
+    /// Amount of leaves of the CMT.
+    pub leaves: u64,
+    /// Accumulator before the leaf, none for the first one.
+    pub previous: Option<Hash>,
+    /// Hashes of the leaves appended after the leaf, from oldest to newest.
+    pub next_leaves: Vec<Hash>,
+}
+
+impl<H: Hasher> Cmt<H> {
+    /// Create an empty CMT.
+    pub fn new() -> Self {
+        Self {
+            leaves: vec![],
+            accumulators: vec![],
+            hasher: PhantomData,
+        }
+    }
+
+    /// Amount of leaves.
+    pub fn len(&self) -> u64 {
+        self.leaves.len() as u64
+    }
+
+    /// Check if the CMT has no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.leaves.is_empty()
+    }
+
+    /// Append a leaf containing given data, returning its index.
+    pub fn append(&mut self, data: &[u8]) -> u64 {
+        let leaf = H::leaf(data);
+
+        let accumulator = match self.accumulators.last() {
+            Some(previous) => H::node(previous, &leaf),
+            None => leaf,
+        };
+
+        self.leaves.push(leaf);
+        self.accumulators.push(accumulator);
+
+        self.len() - 1
+    }
+
+    /// Root committing to all leaves.
+    pub fn root(&self) -> Hash {
+        bag_accumulator::<H>(self.len(), self.accumulators.last())
+    }
+
+    /// Proof of inclusion of a leaf, none if the index is out of bounds.
+    pub fn proof(&self, leaf_index: u64) -> Option<Proof> {
+        if leaf_index >= self.len() {
+            return None;
+        }
+
+        let i = leaf_index as usize;
+
+        Some(Proof {
+            leaf_index,
+            leaves: self.len(),
+            previous: i.checked_sub(1).map(|p| self.accumulators[p]),
+            next_leaves: self.leaves[i + 1..].to_vec(),
+        })
+    }
+}
+
+impl<H: Hasher> Default for Cmt<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Proof {
+    /// Check that the leaf containing given data is included in the CMT of
+    /// given root.
+    pub fn verify<H: Hasher>(&self, root: &Hash, data: &[u8]) -> bool {
+        if self.leaf_index >= self.leaves
+            || self.next_leaves.len() as u64 != self.leaves - self.leaf_index - 1
+            || self.previous.is_some() != (self.leaf_index > 0)
+        {
+            return false;
+        }
+
+        let leaf = H::leaf(data);
+
+        let mut accumulator = match &self.previous {
+            Some(previous) => H::node(previous, &leaf),
+            None => leaf,
+        };
+
+        for next_leaf in &self.next_leaves {
+            accumulator = H::node(&accumulator, next_leaf);
+        }
+
+        bag_accumulator::<H>(self.leaves, Some(&accumulator)) == *root
+    }
+
+    /// Amount of hashes in the proof.
+    pub fn hashes(&self) -> usize {
+        self.previous.iter().count() + self.next_leaves.len()
+    }
+
+    /// Size of the proof in bytes, leaf index and amount of leaves included.
+    pub fn size(&self) -> usize {
+        self.hashes() * std::mem::size_of::<Hash>() + 2 * std::mem::size_of::<u64>()
+    }
+}
+
+/// Root of a CMT of given size and last accumulator.
+fn bag_accumulator<H: Hasher>(leaves: u64, accumulator: Option<&Hash>) -> Hash {
+    let accumulator = accumulator.copied().unwrap_or([0; 32]);
+
+    H::hash(&[b"cmt", &leaves.to_be_bytes(), &accumulator])
+}
@@ -0,0 +1,54 @@
+//! Hash functions of Merkle structures, using the same sha3 and blake3 crates
+//! as the weight formulas.
+
+use crate::Hash;
+use sha3::{Digest, Sha3_256};
+
+/// Prefix of leaves hashes, so a leaf can't be taken for a node.
+const LEAF_PREFIX: u8 = 0;
+/// Prefix of nodes hashes.
+const NODE_PREFIX: u8 = 1;
+
+/// Hash function of a Merkle structure.
+pub trait Hasher {
+    /// Hash of the concatenation of given parts.
+    fn hash(parts: &[&[u8]]) -> Hash;
+
+    /// Hash of a leaf containing given data.
+    fn leaf(data: &[u8]) -> Hash {
+        Self::hash(&[&[LEAF_PREFIX], data])
+    }
+
+    /// Hash of a node from the hashes of its children.
+    fn node(left: &Hash, right: &Hash) -> Hash {
+        Self::hash(&[&[NODE_PREFIX], left, right])
+    }
+}
+
+/// SHA3-256.
+#[derive(Debug, Clone, Copy)]
+pub struct Sha3;
+
+impl Hasher for Sha3 {
+    fn hash(parts: &[&[u8]]) -> Hash {
+        let mut hasher = Sha3_256::new();
+        for part in parts {
+            hasher.input(part);
+        }
+        hasher.result().into()
+    }
+}
+
+/// BLAKE3.
+#[derive(Debug, Clone, Copy)]
+pub struct Blake3;
+
+impl Hasher for Blake3 {
+    fn hash(parts: &[&[u8]]) -> Hash {
+        let mut hasher = blake3::Hasher::new();
+        for part in parts {
+            hasher.update(part);
+        }
+        *hasher.finalize().as_bytes()
+    }
+}
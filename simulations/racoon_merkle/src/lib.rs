@@ -0,0 +1,9 @@
+//! Merkle structures committing to the events emitted by a chain, such as
+//! `BeaconWorld.events_mmr` or `BeaconWorld.events_cmt`.
+
+pub mod cmt;
+pub mod hasher;
+pub mod mmr;
+
+/// Hash of a node.
+pub type Hash = [u8; 32];
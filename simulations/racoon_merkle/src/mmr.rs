@@ -0,0 +1,186 @@
+//! Merkle Mountain Range : append-only list of perfect binary trees ("peaks")
+//! of decreasing heights, one for each bit set in the amount of leaves.
+//! Appending a leaf merges the trees of equal height, like a binary counter.
+//!
+//! The root bags the peaks from right to left, `node(p0, node(p1, p2))` for 3
+//! peaks, then commits to the amount of leaves so that MMRs of different sizes
+//! can't share a root.
+
+use crate::{hasher::Hasher, Hash};
+use std::marker::PhantomData;
+
+/// Merkle Mountain Range of events hashed with `H`.
+#[derive(Debug, Clone)]
+pub struct Mmr<H: Hasher> {
+    /// Hashes of the nodes of each height, leaves being height 0. The node `i`
+    /// of height `h` is the parent of nodes `2i` and `2i + 1` of height `h - 1`.
+    levels: Vec<Vec<Hash>>,
+    hasher: PhantomData<H>,
+}
+
+/// Proof that a leaf is included in a MMR of given size.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proof {
+    /// Index of the leaf.
+    pub leaf_index: u64,
+    /// Amount of leaves of the MMR.
+    pub leaves: u64,
+    /// Siblings from the leaf up to its peak.
+    pub siblings: Vec<Hash>,
+    /// Other peaks, from left to right.
+    pub peaks: Vec<Hash>,
+}
+
+impl<H: Hasher> Mmr<H> {
+    /// Create an empty MMR.
+    pub fn new() -> Self {
+        Self {
+            levels: vec![vec![]],
+            hasher: PhantomData,
+        }
+    }
+
+    /// Amount of leaves.
+    pub fn len(&self) -> u64 {
+        self.levels[0].len() as u64
+    }
+
+    /// Check if the MMR has no leaves.
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Append a leaf containing given data, returning its index.
+    pub fn append(&mut self, data: &[u8]) -> u64 {
+        self.levels[0].push(H::leaf(data));
+
+        // Merge the last 2 nodes of a height while they are siblings.
+        let mut height = 0;
+        while self.levels[height].len().is_multiple_of(2) {
+            let level = &self.levels[height];
+            let node = H::node(&level[level.len() - 2], &level[level.len() - 1]);
+
+            if height + 1 == self.levels.len() {
+                self.levels.push(vec![]);
+            }
+
+            self.levels[height + 1].push(node);
+            height += 1;
+        }
+
+        self.len() - 1
+    }
+
+    /// Hashes of the peaks, from left (highest) to right.
+    pub fn peaks(&self) -> Vec<Hash> {
+        peak_heights(self.len())
+            .into_iter()
+            .map(|height| *self.levels[height as usize].last().unwrap())
+            .collect()
+    }
+
+    /// Root committing to all leaves.
+    pub fn root(&self) -> Hash {
+        bag_peaks::<H>(self.len(), &self.peaks())
+    }
+
+    /// Proof of inclusion of a leaf, none if the index is out of bounds.
+    pub fn proof(&self, leaf_index: u64) -> Option<Proof> {
+        let leaves = self.len();
+        let (peak, height) = leaf_peak(leaves, leaf_index)?;
+
+        let siblings = (0..height)
+            .map(|h| self.levels[h as usize][((leaf_index >> h) ^ 1) as usize])
+            .collect();
+
+        let mut peaks = self.peaks();
+        peaks.remove(peak);
+
+        Some(Proof {
+            leaf_index,
+            leaves,
+            siblings,
+            peaks,
+        })
+    }
+}
+
+impl<H: Hasher> Default for Mmr<H> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Proof {
+    /// Check that the leaf containing given data is included in the MMR of
+    /// given root.
+    pub fn verify<H: Hasher>(&self, root: &Hash, data: &[u8]) -> bool {
+        let (peak, height) = match leaf_peak(self.leaves, self.leaf_index) {
+            Some(res) => res,
+            None => return false,
+        };
+
+        if self.siblings.len() != height as usize
+            || self.peaks.len() + 1 != peak_heights(self.leaves).len()
+        {
+            return false;
+        }
+
+        let mut hash = H::leaf(data);
+
+        for (h, sibling) in self.siblings.iter().enumerate() {
+            hash = if (self.leaf_index >> h) & 1 == 0 {
+                H::node(&hash, sibling)
+            } else {
+                H::node(sibling, &hash)
+            };
+        }
+
+        let mut peaks = self.peaks.clone();
+        peaks.insert(peak, hash);
+
+        bag_peaks::<H>(self.leaves, &peaks) == *root
+    }
+
+    /// Amount of hashes in the proof.
+    pub fn hashes(&self) -> usize {
+        self.siblings.len() + self.peaks.len()
+    }
+
+    /// Size of the proof in bytes, leaf index and amount of leaves included.
+    pub fn size(&self) -> usize {
+        self.hashes() * std::mem::size_of::<Hash>() + 2 * std::mem::size_of::<u64>()
+    }
+}
+
+/// Heights of the peaks of a MMR of given size, from left to right.
+fn peak_heights(leaves: u64) -> Vec<u32> {
+    (0..64).rev().filter(|h| (leaves >> h) & 1 == 1).collect()
+}
+
+/// Position among the peaks and height of the peak containing a leaf.
+fn leaf_peak(leaves: u64, leaf_index: u64) -> Option<(usize, u32)> {
+    let mut first_leaf = 0;
+
+    for (peak, height) in peak_heights(leaves).into_iter().enumerate() {
+        first_leaf += 1 << height;
+
+        if leaf_index < first_leaf {
+            return Some((peak, height));
+        }
+    }
+
+    None
+}
+
+/// Root of a MMR of given size and peaks.
+fn bag_peaks<H: Hasher>(leaves: u64, peaks: &[Hash]) -> Hash {
+    let bagged = peaks
+        .iter()
+        .rev()
+        .copied()
+        .reduce(|right, left| H::node(&left, &right))
+        .unwrap_or([0; 32]);
+
+    H::hash(&[b"mmr", &leaves.to_be_bytes(), &bagged])
+}
@@ -0,0 +1,137 @@
+use proptest::prelude::*;
+use racoon_merkle::{
+    cmt::Cmt,
+    hasher::{Blake3, Hasher, Sha3},
+};
+
+fn cmt<H: Hasher>(events: &[Vec<u8>]) -> Cmt<H> {
+    let mut cmt = Cmt::new();
+    for event in events {
+        cmt.append(event);
+    }
+    cmt
+}
+
+fn events() -> impl Strategy<Value = Vec<Vec<u8>>> {
+    prop::collection::vec(prop::collection::vec(any::<u8>(), 0..16), 1..128)
+}
+
+fn all_proofs_verify<H: Hasher>(events: &[Vec<u8>]) {
+    let cmt = cmt::<H>(events);
+    let root = cmt.root();
+
+    for (i, event) in events.iter().enumerate() {
+        let proof = cmt.proof(i as u64).unwrap();
+        assert!(proof.verify::<H>(&root, event), "leaf {}", i);
+    }
+}
+
+proptest! {
+    #[test]
+    fn proofs_verify_sha3(events in events()) {
+        all_proofs_verify::<Sha3>(&events);
+    }
+
+    #[test]
+    fn proofs_verify_blake3(events in events()) {
+        all_proofs_verify::<Blake3>(&events);
+    }
+
+    #[test]
+    fn append_returns_index(events in events()) {
+        let mut cmt = Cmt::<Sha3>::new();
+
+        for (i, event) in events.iter().enumerate() {
+            prop_assert_eq!(cmt.append(event), i as u64);
+        }
+
+        prop_assert_eq!(cmt.len(), events.len() as u64);
+        prop_assert!(cmt.proof(cmt.len()).is_none());
+    }
+
+    #[test]
+    fn wrong_data_is_rejected(events in events(), index in any::<prop::sample::Index>()) {
+        let cmt = cmt::<Sha3>(&events);
+        let i = index.index(events.len());
+        let proof = cmt.proof(i as u64).unwrap();
+
+        let mut data = events[i].clone();
+        data.push(0);
+
+        prop_assert!(!proof.verify::<Sha3>(&cmt.root(), &data));
+    }
+
+    #[test]
+    fn wrong_index_is_rejected(events in events(), index in any::<prop::sample::Index>()) {
+        let cmt = cmt::<Sha3>(&events);
+        let i = index.index(events.len());
+        let mut proof = cmt.proof(i as u64).unwrap();
+
+        // The amount of newer leaves no longer matches the index.
+        proof.leaf_index ^= 1;
+        prop_assert!(!proof.verify::<Sha3>(&cmt.root(), &events[i]));
+    }
+
+    #[test]
+    fn tampered_proof_is_rejected(
+        events in events(),
+        index in any::<prop::sample::Index>(),
+        hash in any::<prop::sample::Index>(),
+    ) {
+        let cmt = cmt::<Blake3>(&events);
+        let i = index.index(events.len());
+        let mut proof = cmt.proof(i as u64).unwrap();
+        prop_assume!(proof.hashes() > 0);
+
+        let h = hash.index(proof.hashes());
+        match proof.previous.as_mut() {
+            Some(previous) if h == 0 => previous[0] ^= 1,
+            Some(_) => proof.next_leaves[h - 1][0] ^= 1,
+            None => proof.next_leaves[h][0] ^= 1,
+        }
+
+        prop_assert!(!proof.verify::<Blake3>(&cmt.root(), &events[i]));
+    }
+
+    #[test]
+    fn old_root_rejects_new_proofs(events in events(), event in prop::collection::vec(any::<u8>(), 0..16)) {
+        let mut cmt = cmt::<Sha3>(&events);
+        let old_root = cmt.root();
+        let i = cmt.append(&event);
+
+        prop_assert_ne!(cmt.root(), old_root);
+        prop_assert!(!cmt.proof(0).unwrap().verify::<Sha3>(&old_root, &events[0]));
+        prop_assert!(cmt.proof(i).unwrap().verify::<Sha3>(&cmt.root(), &event));
+    }
+
+    #[test]
+    fn proof_size_grows_with_age(events in events(), index in any::<prop::sample::Index>()) {
+        let cmt = cmt::<Sha3>(&events);
+        let i = index.index(events.len()) as u64;
+        let proof = cmt.proof(i).unwrap();
+
+        // The accumulator before the leaf, then one hash per newer leaf.
+        let age = (cmt.len() - 1 - i) as usize;
+        prop_assert_eq!(proof.hashes(), age + usize::from(i > 0));
+        prop_assert_eq!(proof.size(), proof.hashes() * 32 + 16);
+    }
+}
+
+#[test]
+fn empty_cmt() {
+    let cmt = Cmt::<Sha3>::new();
+
+    assert!(cmt.is_empty());
+    assert!(cmt.proof(0).is_none());
+    assert_ne!(cmt.root(), Cmt::<Blake3>::new().root());
+}
+
+#[test]
+fn newest_proof_is_single_hash() {
+    let cmt = cmt::<Sha3>(&[b"old".to_vec(), b"new".to_vec()]);
+    let proof = cmt.proof(1).unwrap();
+
+    assert_eq!(proof.hashes(), 1);
+    assert!(proof.verify::<Sha3>(&cmt.root(), b"new"));
+    assert!(!proof.verify::<Sha3>(&cmt.root(), b"old"));
+}
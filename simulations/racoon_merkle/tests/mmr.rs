@@ -0,0 +1,141 @@
+use proptest::prelude::*;
+use racoon_merkle::{
+    hasher::{Blake3, Hasher, Sha3},
+    mmr::Mmr,
+};
+
+fn mmr<H: Hasher>(events: &[Vec<u8>]) -> Mmr<H> {
+    let mut mmr = Mmr::new();
+    for event in events {
+        mmr.append(event);
+    }
+    mmr
+}
+
+fn events() -> impl Strategy<Value = Vec<Vec<u8>>> {
+    prop::collection::vec(prop::collection::vec(any::<u8>(), 0..16), 1..128)
+}
+
+fn all_proofs_verify<H: Hasher>(events: &[Vec<u8>]) {
+    let mmr = mmr::<H>(events);
+    let root = mmr.root();
+
+    for (i, event) in events.iter().enumerate() {
+        let proof = mmr.proof(i as u64).unwrap();
+        assert!(proof.verify::<H>(&root, event), "leaf {}", i);
+    }
+}
+
+proptest! {
+    #[test]
+    fn proofs_verify_sha3(events in events()) {
+        all_proofs_verify::<Sha3>(&events);
+    }
+
+    #[test]
+    fn proofs_verify_blake3(events in events()) {
+        all_proofs_verify::<Blake3>(&events);
+    }
+
+    #[test]
+    fn append_returns_index(events in events()) {
+        let mut mmr = Mmr::<Sha3>::new();
+
+        for (i, event) in events.iter().enumerate() {
+            prop_assert_eq!(mmr.append(event), i as u64);
+        }
+
+        prop_assert_eq!(mmr.len(), events.len() as u64);
+        prop_assert_eq!(mmr.peaks().len() as u32, mmr.len().count_ones());
+        prop_assert!(mmr.proof(mmr.len()).is_none());
+    }
+
+    #[test]
+    fn wrong_data_is_rejected(events in events(), index in any::<prop::sample::Index>()) {
+        let mmr = mmr::<Sha3>(&events);
+        let i = index.index(events.len());
+        let proof = mmr.proof(i as u64).unwrap();
+
+        let mut data = events[i].clone();
+        data.push(0);
+
+        prop_assert!(!proof.verify::<Sha3>(&mmr.root(), &data));
+    }
+
+    #[test]
+    fn wrong_index_is_rejected(events in events(), index in any::<prop::sample::Index>()) {
+        let mmr = mmr::<Sha3>(&events);
+        let i = index.index(events.len());
+        let mut proof = mmr.proof(i as u64).unwrap();
+
+        // Any other leaf of the same peak uses siblings on the other side.
+        proof.leaf_index ^= 1;
+        if events.get(proof.leaf_index as usize) != Some(&events[i]) {
+            prop_assert!(!proof.verify::<Sha3>(&mmr.root(), &events[i]));
+        }
+    }
+
+    #[test]
+    fn tampered_proof_is_rejected(
+        events in events(),
+        index in any::<prop::sample::Index>(),
+        hash in any::<prop::sample::Index>(),
+    ) {
+        let mmr = mmr::<Blake3>(&events);
+        let i = index.index(events.len());
+        let mut proof = mmr.proof(i as u64).unwrap();
+        prop_assume!(proof.hashes() > 0);
+
+        let h = hash.index(proof.hashes());
+        if h < proof.siblings.len() {
+            proof.siblings[h][0] ^= 1;
+        } else {
+            proof.peaks[h - proof.siblings.len()][0] ^= 1;
+        }
+
+        prop_assert!(!proof.verify::<Blake3>(&mmr.root(), &events[i]));
+    }
+
+    #[test]
+    fn old_root_rejects_new_proofs(events in events(), event in prop::collection::vec(any::<u8>(), 0..16)) {
+        let mut mmr = mmr::<Sha3>(&events);
+        let old_root = mmr.root();
+        let i = mmr.append(&event);
+
+        prop_assert_ne!(mmr.root(), old_root);
+        prop_assert!(!mmr.proof(0).unwrap().verify::<Sha3>(&old_root, &events[0]));
+        prop_assert!(mmr.proof(i).unwrap().verify::<Sha3>(&mmr.root(), &event));
+    }
+
+    #[test]
+    fn proof_size_is_logarithmic(events in events(), index in any::<prop::sample::Index>()) {
+        let mmr = mmr::<Sha3>(&events);
+        let proof = mmr.proof(index.index(events.len()) as u64).unwrap();
+
+        // Siblings below the highest peak, then one hash per other peak.
+        let log2 = 63 - mmr.len().leading_zeros() as usize;
+        let peaks = mmr.len().count_ones() as usize;
+        prop_assert!(proof.siblings.len() <= log2);
+        prop_assert!(proof.hashes() < log2 + peaks);
+        prop_assert_eq!(proof.size(), proof.hashes() * 32 + 16);
+    }
+}
+
+#[test]
+fn empty_mmr() {
+    let mmr = Mmr::<Sha3>::new();
+
+    assert!(mmr.is_empty());
+    assert!(mmr.peaks().is_empty());
+    assert!(mmr.proof(0).is_none());
+    assert_ne!(mmr.root(), Mmr::<Blake3>::new().root());
+}
+
+#[test]
+fn single_leaf_proof_is_empty() {
+    let mmr = mmr::<Sha3>(&[b"event".to_vec()]);
+    let proof = mmr.proof(0).unwrap();
+
+    assert_eq!(proof.hashes(), 0);
+    assert!(proof.verify::<Sha3>(&mmr.root(), b"event"));
+}
@@ -0,0 +1,177 @@
+// Cargo run --release --bin address_grinding > address_grinding.csv
+//
+// An attacker with power p controls M validator identities and can move its
+// whole stake to any of them. The seed is revealed k blocks in advance, so at
+// the start of each window of k blocks the attacker knows all weights of the
+// window, while a stake move has to be committed d blocks before it is used
+// (the "Ère" of the journal).
+// When committing, the attacker only knows the weights of the first k - d
+// blocks of the window : it puts its stake on the identity winning the most of
+// them, and wins the remaining d blocks by chance. If d >= k, it commits
+// blindly and keeps its first identity.
+//
+// Outputs the attacker win rate as CSV, along with the blind one. The boost is
+// the win rate over the fair share of the attacker, its power.
+
+use racoon_weight::{powers, seed, weight_formula, StakeDistribution};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use rug::{float::Special, Float};
+use serde::Serialize;
+
+/// Amount of honest validators.
+const VALIDATORS: usize = 100;
+/// Number of simulated blocks.
+const HEIGHTS: u64 = 10_000;
+/// Precisions in bits of the floating point numbers.
+const FLOAT_PRECISION: u32 = 53;
+/// Distribution of honest validators stakes.
+const STAKE_DISTRIBUTION: StakeDistribution = StakeDistribution::Hash { spread_factor: 20 };
+/// Weight formulas to test.
+const FORMULAS: &[&str] = &["exp", "log"];
+/// Total power of the attacker.
+const ATTACKER_POWERS: &[f64] = &[0.01, 0.1, 0.3];
+/// Amount of identities the attacker can move its stake to.
+const IDENTITIES: &[usize] = &[1, 2, 4, 8, 16];
+/// Amount of blocks the seed is known in advance, which is also the length
+/// of the windows the attacker chooses an identity for.
+const KNOWN_BLOCKS: &[u64] = &[10, 100];
+/// Amount of blocks a stake move is committed before being used.
+const COMMITMENT_DELAYS: &[u64] = &[0, 50, 200];
+
+/// Line of the CSV output.
+#[derive(Serialize)]
+struct Line {
+    formula: &'static str,
+    power: f64,
+    identities: usize,
+    known_blocks: u64,
+    commitment_delay: u64,
+    ground_blocks: u64,
+    win_rate: f64,
+    blind_win_rate: f64,
+    boost: f64,
+}
+
+fn main() {
+    let honest_powers = powers(&STAKE_DISTRIBUTION, VALIDATORS, FLOAT_PRECISION);
+    let max_identities = *IDENTITIES.iter().max().unwrap();
+    let seed = seed(0);
+
+    let progress = ProgressBar::new((FORMULAS.len() * ATTACKER_POWERS.len()) as u64 * HEIGHTS);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Computing weights: [{elapsed} - {eta}] [{wide_bar}] Height {pos}/{len}")
+            .progress_chars("=> "),
+    );
+
+    let mut lines = vec![];
+
+    for &formula_name in FORMULAS {
+        let formula = weight_formula(formula_name).unwrap();
+
+        for &power in ATTACKER_POWERS {
+            // Honest validators share the remaining power.
+            let honest_share = Float::with_val(FLOAT_PRECISION, 1.0 - power);
+            let honest_powers: Vec<_> = honest_powers
+                .iter()
+                .map(|p| Float::with_val(FLOAT_PRECISION, p * &honest_share))
+                .collect();
+            let attacker_power = Float::with_val(FLOAT_PRECISION, power);
+
+            // wins[h][i] : identity i holding the attacker stake wins height h
+            let wins: Vec<Vec<bool>> = (0..HEIGHTS)
+                .into_par_iter()
+                .map(|height| {
+                    let mut best_honest = Float::with_val(FLOAT_PRECISION, Special::NegInfinity);
+
+                    for (validator, power) in honest_powers.iter().enumerate() {
+                        let weight =
+                            formula(&seed, power, height, 0, validator as u64, FLOAT_PRECISION);
+                        best_honest.max_mut(&weight);
+                    }
+
+                    // Identities come after honest validators ids.
+                    let wins = (0..max_identities)
+                        .map(|identity| {
+                            let validator = (VALIDATORS + identity) as u64;
+                            formula(
+                                &seed,
+                                &attacker_power,
+                                height,
+                                0,
+                                validator,
+                                FLOAT_PRECISION,
+                            ) > best_honest
+                        })
+                        .collect();
+
+                    progress.inc(1);
+                    wins
+                })
+                .collect();
+
+            let blind_wins = wins.iter().filter(|w| w[0]).count();
+            let blind_win_rate = blind_wins as f64 / HEIGHTS as f64;
+
+            for &identities in IDENTITIES {
+                for &known_blocks in KNOWN_BLOCKS {
+                    for &commitment_delay in COMMITMENT_DELAYS {
+                        let ground_blocks = known_blocks.saturating_sub(commitment_delay);
+                        let attacker_wins =
+                            ground_wins(&wins, identities, known_blocks, ground_blocks);
+                        let win_rate = attacker_wins as f64 / HEIGHTS as f64;
+
+                        lines.push(Line {
+                            formula: formula_name,
+                            power,
+                            identities,
+                            known_blocks,
+                            commitment_delay,
+                            ground_blocks,
+                            win_rate,
+                            blind_win_rate,
+                            boost: win_rate / power,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    progress.finish();
+
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+
+    for line in lines {
+        writer.serialize(line).unwrap();
+    }
+
+    writer.flush().unwrap();
+}
+
+/// Blocks won when the stake is put, for each window of `window` blocks, on
+/// the identity winning the most of the first `ground` blocks of the window.
+fn ground_wins(wins: &[Vec<bool>], identities: usize, window: u64, ground: u64) -> usize {
+    let count =
+        |blocks: &[Vec<bool>], identity: usize| blocks.iter().filter(|w| w[identity]).count();
+
+    wins.chunks(window as usize)
+        .map(|window| {
+            let known = &window[..std::cmp::min(ground as usize, window.len())];
+
+            // Ties keep the first identity, so nothing is ground without
+            // known blocks.
+            let best = (0..identities).fold(0, |best, identity| {
+                if count(known, identity) > count(known, best) {
+                    identity
+                } else {
+                    best
+                }
+            });
+
+            count(window, best)
+        })
+        .sum()
+}
@@ -1,43 +1,141 @@
-// Cargo run --release
+// Cargo run --release --bin basic_winrates -- [--config basic_winrates.ron] [overrides]
+//
+// Settings are read from a RON file and can be overridden from the command
+// line, see `--help`.
 
-use racoon_weight::{weight_exp, Config};
+use racoon_weight::{powers, weight_formula, Config, StakeDistribution};
 
 use indicatif::{ProgressBar, ProgressStyle};
-use rug::{integer::Order, ops::Pow, Float, Integer};
-use sha3::{Digest, Sha3_256};
-
-/// Amount of validators.
-const VALIDATORS: usize = 1000;
-/// Number of shards.
-const SHARDS: u64 = 1;
-/// Number of epochs.
-const EPOCHS: u64 = 1;
-/// Number of blocks in 1 epoch.
-const HEIGHTS_PER_EPOCH: u64 = 1000;
-/// Precisions in bits of the floating point numbers.
-const FLOAT_PRECISION: u32 = 53;
-/// Pread factor. Higher number will result in greater differencies between
-/// biggest validators and the others.
-const STAKE_SPREAD_FACTOR: u32 = 20;
+use rug::Float;
+use serde::Deserialize;
+use std::{fs::File, path::PathBuf};
+use structopt::StructOpt;
+
+/// Binary settings.
+#[derive(Clone, Debug, Deserialize)]
+struct Settings {
+    /// Amount of validators.
+    validators: usize,
+    /// Number of shards.
+    shards: u64,
+    /// Number of epochs.
+    epochs: u64,
+    /// Number of blocks in 1 epoch.
+    heights_per_epoch: u64,
+    /// Precisions in bits of the floating point numbers.
+    float_precision: u32,
+    /// Distribution of validators stakes.
+    stake_distribution: StakeDistribution,
+    /// Name of the weight formula (`exp`, `log`, `fixed`).
+    weight_formula: String,
+    /// Amount of validators displayed in the results.
+    top: usize,
+    /// File where results are written, as JSON or CSV depending on its
+    /// extension.
+    output: Option<PathBuf>,
+}
+
+/// Command line options, overriding the settings file.
+#[derive(Debug, StructOpt)]
+struct Options {
+    /// RON settings file.
+    #[structopt(long, default_value = "basic_winrates.ron")]
+    config: PathBuf,
+    /// Amount of validators.
+    #[structopt(long)]
+    validators: Option<usize>,
+    /// Number of shards.
+    #[structopt(long)]
+    shards: Option<u64>,
+    /// Number of epochs.
+    #[structopt(long)]
+    epochs: Option<u64>,
+    /// Number of blocks in 1 epoch.
+    #[structopt(long)]
+    heights_per_epoch: Option<u64>,
+    /// Precisions in bits of the floating point numbers.
+    #[structopt(long)]
+    float_precision: Option<u32>,
+    /// Distribution of validators stakes, in RON (ex: "Zipf(exponent: 1.0)").
+    #[structopt(long, parse(try_from_str = ron::de::from_str))]
+    stake_distribution: Option<StakeDistribution>,
+    /// Name of the weight formula (`exp`, `log`, `fixed`).
+    #[structopt(long)]
+    weight_formula: Option<String>,
+    /// Amount of validators displayed in the results.
+    #[structopt(long)]
+    top: Option<usize>,
+    /// File where results are written (.json or .csv).
+    #[structopt(long)]
+    output: Option<PathBuf>,
+}
+
+impl Settings {
+    /// Read the settings file and apply command line overrides.
+    fn load(options: Options) -> Self {
+        let mut settings: Settings =
+            ron::de::from_reader(File::open(&options.config).unwrap()).unwrap();
+
+        if let Some(validators) = options.validators {
+            settings.validators = validators;
+        }
+        if let Some(shards) = options.shards {
+            settings.shards = shards;
+        }
+        if let Some(epochs) = options.epochs {
+            settings.epochs = epochs;
+        }
+        if let Some(heights_per_epoch) = options.heights_per_epoch {
+            settings.heights_per_epoch = heights_per_epoch;
+        }
+        if let Some(float_precision) = options.float_precision {
+            settings.float_precision = float_precision;
+        }
+        if let Some(stake_distribution) = options.stake_distribution {
+            settings.stake_distribution = stake_distribution;
+        }
+        if let Some(weight_formula) = options.weight_formula {
+            settings.weight_formula = weight_formula;
+        }
+        if let Some(top) = options.top {
+            settings.top = top;
+        }
+        if let Some(output) = options.output {
+            settings.output = Some(output);
+        }
+
+        settings
+    }
+}
 
 fn main() {
-    println!("Validators: {}", VALIDATORS);
-    println!("Shards: {}", SHARDS);
-    println!("Epochs: {}", EPOCHS);
-    println!("Blocks per epoch: {}", HEIGHTS_PER_EPOCH);
-    println!("Float precision: {}", FLOAT_PRECISION);
-    println!("Stake spread factor: {}", STAKE_SPREAD_FACTOR);
+    let settings = Settings::load(Options::from_args());
+
+    let formula = weight_formula(&settings.weight_formula)
+        .unwrap_or_else(|| panic!("unknown weight formula {:?}", settings.weight_formula));
+
+    println!("Validators: {}", settings.validators);
+    println!("Shards: {}", settings.shards);
+    println!("Epochs: {}", settings.epochs);
+    println!("Blocks per epoch: {}", settings.heights_per_epoch);
+    println!("Float precision: {}", settings.float_precision);
+    println!("Stake distribution: {:?}", settings.stake_distribution);
+    println!("Weight formula: {}", settings.weight_formula);
     println!();
 
-    let powers = powers();
-    simulate(&powers, &weight_exp);
+    let powers = powers(
+        &settings.stake_distribution,
+        settings.validators,
+        settings.float_precision,
+    );
+    simulate(&settings, &powers, &formula);
 }
 
-fn simulate<W>(powers: &[Float], formula: &W)
+fn simulate<W>(settings: &Settings, powers: &[Float], formula: &W)
 where
     W: Sync + Fn(&[u8], &Float, u64, u64, u64, u32) -> Float,
 {
-    let progress = ProgressBar::new((EPOCHS * HEIGHTS_PER_EPOCH) as u64);
+    let progress = ProgressBar::new(settings.epochs * settings.heights_per_epoch);
     progress.set_style(
         ProgressStyle::default_bar()
             .template("Simulating POS: [{elapsed} - {eta}] [{wide_bar}] Height {pos}/{len}")
@@ -45,57 +143,31 @@ where
     );
 
     let config = Config {
-        powers: &powers,
+        powers,
         weight: formula,
         progress: || progress.inc(1),
-        validators: VALIDATORS,
-        shards: SHARDS,
-        epochs: EPOCHS,
-        blocks_per_epoch: HEIGHTS_PER_EPOCH,
-        precision: FLOAT_PRECISION,
+        validators: settings.validators,
+        shards: settings.shards,
+        epochs: settings.epochs,
+        blocks_per_epoch: settings.heights_per_epoch,
+        precision: settings.float_precision,
     };
 
     let result = config.simulate_full();
 
     progress.finish();
     println!();
-    result.display(&powers, 10, FLOAT_PRECISION);
-}
-
-/// Generate the stake of a validator.
-fn stake(id: u64) -> Float {
-    let mut hasher = Sha3_256::new();
-    hasher.input(b"validator");
-    hasher.input(id.to_be_bytes());
-    let stake = hasher.result();
-
-    let stake = Integer::from_digits(&stake, Order::Lsf);
-    let stake = Float::with_val(FLOAT_PRECISION, stake);
-
-    let hash_max = Float::with_val(FLOAT_PRECISION, 2).pow(256);
-
-    let stake: Float = stake / hash_max;
-    let stake: Float = stake * 10;
-    let stake = stake.pow(STAKE_SPREAD_FACTOR);
-
-    1 + stake
-}
-
-/// Generate validators powers.
-fn powers() -> Vec<Float> {
-    let mut stakes = vec![Float::with_val(FLOAT_PRECISION, 0); VALIDATORS];
-    let mut stakes_sum = Float::with_val(FLOAT_PRECISION, 0);
+    result.display(
+        powers,
+        settings.top.min(settings.validators),
+        settings.float_precision,
+    );
 
-    // generate stakes and stakes sum
-    for (i, s) in stakes.iter_mut().enumerate() {
-        let stake = stake(i as u64);
-        stakes_sum += stake.clone();
-        *s = stake;
+    if let Some(output) = &settings.output {
+        result
+            .report(powers, settings.float_precision)
+            .write(output);
+        println!();
+        println!("Report written to {}", output.display());
     }
-
-    stakes.sort_by(|a, b| b.partial_cmp(a).unwrap()); // highest first
-    stakes
-        .into_iter()
-        .map(|s| Float::with_val(FLOAT_PRECISION, s) / stakes_sum.clone())
-        .collect()
 }
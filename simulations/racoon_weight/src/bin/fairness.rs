@@ -0,0 +1,105 @@
+// Cargo run --release --bin fairness -- [--formula exp|log|fixed] [--significance 0.01]
+//
+// Check that validators win rates match their powers. Exits with a non-zero
+// status when the chi-squared test rejects the formula at the given
+// significance level (default 0.01).
+
+use racoon_weight::{powers, weight_formula, Config, StakeDistribution};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use structopt::StructOpt;
+
+/// Amount of validators.
+const VALIDATORS: usize = 1000;
+/// Number of shards.
+const SHARDS: u64 = 1;
+/// Number of epochs.
+const EPOCHS: u64 = 10;
+/// Number of blocks in 1 epoch.
+const HEIGHTS_PER_EPOCH: u64 = 1000;
+/// Precisions in bits of the floating point numbers.
+const FLOAT_PRECISION: u32 = 53;
+/// Distribution of validators stakes. Higher spread factor will result in
+/// greater differencies between biggest validators and the others.
+const STAKE_DISTRIBUTION: StakeDistribution = StakeDistribution::Hash { spread_factor: 20 };
+
+/// Command line options.
+#[derive(Debug, StructOpt)]
+struct Options {
+    /// Weight formula (exp, log or fixed).
+    #[structopt(long, default_value = "exp")]
+    formula: String,
+    /// Significance level of the chi-squared test.
+    #[structopt(long, default_value = "0.01")]
+    significance: f64,
+}
+
+fn main() {
+    let Options {
+        formula: formula_name,
+        significance,
+    } = Options::from_args();
+    let formula = weight_formula(&formula_name)
+        .unwrap_or_else(|| panic!("unknown weight formula {:?}", formula_name));
+
+    println!("Weight formula: {}", formula_name);
+    println!("Significance level: {}", significance);
+    println!("Validators: {}", VALIDATORS);
+    println!("Shards: {}", SHARDS);
+    println!("Epochs: {}", EPOCHS);
+    println!("Blocks per epoch: {}", HEIGHTS_PER_EPOCH);
+    println!("Float precision: {}", FLOAT_PRECISION);
+    println!("Stake distribution: {:?}", STAKE_DISTRIBUTION);
+    println!();
+
+    let powers = powers(&STAKE_DISTRIBUTION, VALIDATORS, FLOAT_PRECISION);
+
+    let progress = ProgressBar::new(EPOCHS * HEIGHTS_PER_EPOCH);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Simulating POS: [{elapsed} - {eta}] [{wide_bar}] Height {pos}/{len}")
+            .progress_chars("=> "),
+    );
+
+    let config = Config {
+        powers: &powers,
+        weight: &formula,
+        progress: || progress.inc(1),
+        validators: VALIDATORS,
+        shards: SHARDS,
+        epochs: EPOCHS,
+        blocks_per_epoch: HEIGHTS_PER_EPOCH,
+        precision: FLOAT_PRECISION,
+    };
+
+    let result = config.simulate_full();
+
+    progress.finish();
+    println!();
+    result.display(&powers, 10, FLOAT_PRECISION);
+    println!();
+
+    let outside = result
+        .confidence_intervals(significance)
+        .iter()
+        .zip(powers.iter())
+        .filter(|((low, high), power)| {
+            let power = power.to_f64();
+            power < *low || power > *high
+        })
+        .count();
+
+    println!(
+        "powers outside their {}% interval : {} / {}",
+        (1.0 - significance) * 100.0,
+        outside,
+        VALIDATORS
+    );
+
+    if result.is_fair(&powers, significance) {
+        println!("FAIR : win rates are consistent with powers");
+    } else {
+        println!("UNFAIR : win rates deviate from powers");
+        std::process::exit(1);
+    }
+}
@@ -0,0 +1,144 @@
+// Cargo run --release --bin fixed_divergence
+//
+// Compare winners chosen by the float formula `weight_exp` at
+// `FLOAT_PRECISION` bits and the fixed-point formula `weight_fixed`, which
+// always computes with 64 fractional bits whatever the precision.
+
+use racoon_weight::{powers, seed, weight_exp, weight_fixed, Config, StakeDistribution};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+
+/// Amount of validators.
+const VALIDATORS: usize = 1000;
+/// Number of shards.
+const SHARDS: u64 = 1;
+/// Number of epochs.
+const EPOCHS: u64 = 1;
+/// Number of blocks in 1 epoch.
+const HEIGHTS_PER_EPOCH: u64 = 1000;
+/// Precisions in bits of the floating point numbers (`weight_exp` only).
+const FLOAT_PRECISION: u32 = 53;
+/// Distribution of validators stakes. Higher spread factor will result in
+/// greater differencies between biggest validators and the others.
+const STAKE_DISTRIBUTION: StakeDistribution = StakeDistribution::Hash { spread_factor: 20 };
+
+fn main() {
+    println!("Validators: {}", VALIDATORS);
+    println!("Shards: {}", SHARDS);
+    println!("Epochs: {}", EPOCHS);
+    println!("Blocks per epoch: {}", HEIGHTS_PER_EPOCH);
+    println!("Float precision: {}", FLOAT_PRECISION);
+    println!("Stake distribution: {:?}", STAKE_DISTRIBUTION);
+    println!();
+
+    let powers = powers(&STAKE_DISTRIBUTION, VALIDATORS, FLOAT_PRECISION);
+
+    // Blocks are computed 3 times : once for comparison and once per formula.
+    let progress = ProgressBar::new(EPOCHS * HEIGHTS_PER_EPOCH * 3);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Simulating POS: [{elapsed} - {eta}] [{wide_bar}] Height {pos}/{len}")
+            .progress_chars("=> "),
+    );
+
+    let float_config = Config {
+        powers: &powers,
+        weight: &weight_exp,
+        progress: || progress.inc(1),
+        validators: VALIDATORS,
+        shards: SHARDS,
+        epochs: EPOCHS,
+        blocks_per_epoch: HEIGHTS_PER_EPOCH,
+        precision: FLOAT_PRECISION,
+    };
+
+    let fixed_config = Config {
+        powers: &powers,
+        weight: &weight_fixed,
+        progress: || progress.inc(1),
+        validators: VALIDATORS,
+        shards: SHARDS,
+        epochs: EPOCHS,
+        blocks_per_epoch: HEIGHTS_PER_EPOCH,
+        precision: FLOAT_PRECISION,
+    };
+
+    // Compare each block winner.
+    let blocks: Vec<_> = (0..EPOCHS)
+        .flat_map(|e| (0..HEIGHTS_PER_EPOCH).map(move |h| (e, h)))
+        .collect();
+
+    let divergences: Vec<_> = blocks
+        .into_par_iter()
+        .flat_map(|(epoch, height)| {
+            let seed = seed(epoch);
+            let divergences: Vec<_> = (0..SHARDS)
+                .filter_map(|shard| {
+                    let (float_winner, float_weight) = float_config.winner(&seed, height, shard);
+                    let (fixed_winner, fixed_weight) = fixed_config.winner(&seed, height, shard);
+
+                    if float_winner == fixed_winner {
+                        None
+                    } else {
+                        Some((
+                            epoch,
+                            height,
+                            shard,
+                            float_winner,
+                            float_weight,
+                            fixed_winner,
+                            fixed_weight,
+                        ))
+                    }
+                })
+                .collect();
+
+            (float_config.progress)();
+            divergences
+        })
+        .collect();
+
+    // Compare aggregated wins.
+    let float_result = float_config.simulate_full();
+    let fixed_result = fixed_config.simulate_full();
+
+    progress.finish();
+    println!();
+
+    for (epoch, height, shard, float_winner, float_weight, fixed_winner, fixed_weight) in
+        &divergences
+    {
+        println!(
+            "epoch {} height {} shard {} : float winner {} ({:0.16}) / fixed winner {} ({:0.16})",
+            epoch,
+            height,
+            shard,
+            float_winner,
+            float_weight.to_f64(),
+            fixed_winner,
+            fixed_weight.to_f64(),
+        );
+    }
+
+    let (max_validator, max_divergence) = float_result
+        .wins
+        .iter()
+        .zip(fixed_result.wins.iter())
+        .map(|(a, b)| a.abs_diff(*b))
+        .enumerate()
+        .max_by_key(|(_, diff)| *diff)
+        .unwrap();
+
+    println!();
+    println!("blocks: {}", float_result.rounds);
+    println!("divergent winners : {}", divergences.len());
+    println!(
+        "divergent winners rate : {:0.8}",
+        divergences.len() as f64 / float_result.rounds as f64
+    );
+    println!(
+        "max wins divergence : {} (validator {})",
+        max_divergence, max_validator
+    );
+}
@@ -0,0 +1,164 @@
+// Cargo run --release --bin precision_check
+//
+// Run the same seeds/heights/shards with multiple float precisions and list
+// every block where the winner differs from the one found with the highest
+// precision.
+
+use racoon_weight::{powers, seed, weight_exp, Config, StakeDistribution};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use rug::Float;
+
+/// Amount of validators.
+const VALIDATORS: usize = 1000;
+/// Number of shards.
+const SHARDS: u64 = 1;
+/// Number of epochs.
+const EPOCHS: u64 = 1;
+/// Number of blocks in 1 epoch.
+const HEIGHTS_PER_EPOCH: u64 = 1000;
+/// Precisions in bits of the floating point numbers to compare.
+/// The last one is used as the reference.
+const FLOAT_PRECISIONS: &[u32] = &[24, 53, 64, 128, 256];
+/// Distribution of validators stakes. Higher spread factor will result in
+/// greater differencies between biggest validators and the others.
+const STAKE_DISTRIBUTION: StakeDistribution = StakeDistribution::Hash { spread_factor: 20 };
+
+/// Block for which the winner changes with the precision.
+struct Divergence {
+    epoch: u64,
+    height: u64,
+    shard: u64,
+    precision: u32,
+    /// Winner and its weight with the tested precision.
+    winner: (usize, Float),
+    /// Winner with the reference precision and its weight with the tested
+    /// precision.
+    reference_winner: (usize, Float),
+}
+
+fn main() {
+    println!("Validators: {}", VALIDATORS);
+    println!("Shards: {}", SHARDS);
+    println!("Epochs: {}", EPOCHS);
+    println!("Blocks per epoch: {}", HEIGHTS_PER_EPOCH);
+    println!("Float precisions: {:?}", FLOAT_PRECISIONS);
+    println!("Stake distribution: {:?}", STAKE_DISTRIBUTION);
+    println!();
+
+    let reference_precision = *FLOAT_PRECISIONS.last().unwrap();
+
+    // Powers are computed with each precision, like validators would do.
+    let powers: Vec<_> = FLOAT_PRECISIONS
+        .iter()
+        .map(|&precision| powers(&STAKE_DISTRIBUTION, VALIDATORS, precision))
+        .collect();
+
+    let progress = ProgressBar::new(EPOCHS * HEIGHTS_PER_EPOCH);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Simulating POS: [{elapsed} - {eta}] [{wide_bar}] Height {pos}/{len}")
+            .progress_chars("=> "),
+    );
+
+    let configs: Vec<_> = FLOAT_PRECISIONS
+        .iter()
+        .zip(powers.iter())
+        .map(|(&precision, powers)| Config {
+            powers,
+            weight: &weight_exp,
+            progress: || progress.inc(1),
+            validators: VALIDATORS,
+            shards: SHARDS,
+            epochs: EPOCHS,
+            blocks_per_epoch: HEIGHTS_PER_EPOCH,
+            precision,
+        })
+        .collect();
+
+    let (reference_config, tested_configs) = configs.split_last().unwrap();
+
+    let blocks: Vec<_> = (0..EPOCHS)
+        .flat_map(|e| (0..HEIGHTS_PER_EPOCH).map(move |h| (e, h)))
+        .collect();
+
+    let divergences: Vec<_> = blocks
+        .into_par_iter()
+        .flat_map(|(epoch, height)| {
+            let seed = seed(epoch);
+            let mut divergences = vec![];
+
+            for shard in 0..SHARDS {
+                let (reference_winner, _) = reference_config.winner(&seed, height, shard);
+
+                for config in tested_configs {
+                    let winner = config.winner(&seed, height, shard);
+
+                    if winner.0 == reference_winner {
+                        continue;
+                    }
+
+                    let reference_weight = (config.weight)(
+                        &seed,
+                        &config.powers[reference_winner],
+                        height,
+                        shard,
+                        reference_winner as u64,
+                        config.precision,
+                    );
+
+                    divergences.push(Divergence {
+                        epoch,
+                        height,
+                        shard,
+                        precision: config.precision,
+                        winner,
+                        reference_winner: (reference_winner, reference_weight),
+                    });
+                }
+            }
+
+            (reference_config.progress)();
+            divergences
+        })
+        .collect();
+
+    progress.finish();
+    println!();
+
+    println!(
+        "Divergences (reference precision {}) :",
+        reference_precision
+    );
+    println!("epoch      height  shard  precision   winner (weight)   reference winner (weight)");
+
+    for d in &divergences {
+        println!(
+            "{:>5} {:>11} {:>6} {:>10} {:>8} ({:e}) {:>8} ({:e})",
+            d.epoch,
+            d.height,
+            d.shard,
+            d.precision,
+            d.winner.0,
+            d.winner.1,
+            d.reference_winner.0,
+            d.reference_winner.1,
+        );
+    }
+
+    println!();
+    println!("blocks: {}", EPOCHS * HEIGHTS_PER_EPOCH * SHARDS);
+
+    for config in tested_configs {
+        let count = divergences
+            .iter()
+            .filter(|d| d.precision == config.precision)
+            .count();
+
+        println!(
+            "precision {} : {} divergent winners",
+            config.precision, count
+        );
+    }
+}
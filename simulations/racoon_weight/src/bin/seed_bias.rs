@@ -0,0 +1,246 @@
+// Cargo run --release --bin seed_bias -- [--formula exp|log|fixed] > seed_bias.csv
+//
+// The seed of each epoch is derived from the hash of the last block of the
+// previous epoch. When an attacker with power p wins the last blocks of an
+// epoch, it can withhold some of them so the best honest validators produce
+// these heights instead, which re-rolls the next seed. It computes its wins in
+// the next epoch for each choice and keeps the best one, withheld blocks being
+// lost.
+//
+// Outputs the attacker win rate with and without withholding as CSV. Both
+// chains diverge after the first withholding, so the gain of each decision is
+// also measured against publishing on the same epoch.
+
+use racoon_weight::{
+    block_hash, next_seed, powers, seed, weight_formula, StakeDistribution, WeightFormula,
+};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use rug::{float::Special, Float};
+use serde::Serialize;
+use structopt::StructOpt;
+
+/// Amount of honest validators.
+const VALIDATORS: usize = 50;
+/// Number of simulated epochs.
+const EPOCHS: u64 = 400;
+/// Number of blocks in 1 epoch.
+const HEIGHTS_PER_EPOCH: u64 = 50;
+/// Precisions in bits of the floating point numbers.
+const FLOAT_PRECISION: u32 = 53;
+/// Distribution of honest validators stakes.
+const STAKE_DISTRIBUTION: StakeDistribution = StakeDistribution::Hash { spread_factor: 20 };
+/// Attacker powers to test.
+const ATTACKER_POWERS: &[f64] = &[0.05, 0.1, 0.2, 0.3];
+
+/// Line of the CSV output.
+#[derive(Serialize)]
+struct Line {
+    power: f64,
+    blocks: u64,
+    honest_win_rate: f64,
+    withholding_win_rate: f64,
+    gain: f64,
+    decision_gain: f64,
+    rerolls: u64,
+    withheld_blocks: u64,
+}
+
+/// Result of a simulation.
+#[derive(Default)]
+struct Outcome {
+    /// Blocks won by the attacker and published.
+    wins: u64,
+    /// Epochs after which the attacker withheld blocks.
+    rerolls: u64,
+    /// Blocks withheld by the attacker.
+    withheld: u64,
+    /// Sum over epochs of the blocks gained by the chosen option compared to
+    /// publishing every block.
+    decision_gain: i64,
+}
+
+/// Blocks withheld at the end of an epoch and their consequences.
+struct Choice {
+    /// Blocks won by the attacker in the next epoch minus the withheld ones.
+    score: i64,
+    withheld: usize,
+    /// Hash of the last block of the epoch.
+    hash: [u8; 32],
+    /// Draws of the next epoch.
+    next_draws: Vec<(u64, bool)>,
+}
+
+/// Command line options.
+#[derive(Debug, StructOpt)]
+struct Options {
+    /// Weight formula (exp, log or fixed).
+    #[structopt(long, default_value = "exp")]
+    formula: String,
+}
+
+fn main() {
+    let formula_name = Options::from_args().formula;
+    let formula = weight_formula(&formula_name)
+        .unwrap_or_else(|| panic!("unknown weight formula {:?}", formula_name));
+
+    let honest_powers = powers(&STAKE_DISTRIBUTION, VALIDATORS, FLOAT_PRECISION);
+
+    let progress = ProgressBar::new(ATTACKER_POWERS.len() as u64 * 2 * EPOCHS);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Simulating epochs: [{elapsed} - {eta}] [{wide_bar}] Epoch {pos}/{len}")
+            .progress_chars("=> "),
+    );
+
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+    let blocks = EPOCHS * HEIGHTS_PER_EPOCH;
+
+    for &power in ATTACKER_POWERS {
+        // Honest validators share the remaining power.
+        let honest_share = Float::with_val(FLOAT_PRECISION, 1.0 - power);
+        let honest_powers: Vec<_> = honest_powers
+            .iter()
+            .map(|p| Float::with_val(FLOAT_PRECISION, p * &honest_share))
+            .collect();
+        let attacker_power = Float::with_val(FLOAT_PRECISION, power);
+
+        let honest = simulate(&honest_powers, &attacker_power, formula, false, &progress);
+        let withholding = simulate(&honest_powers, &attacker_power, formula, true, &progress);
+
+        let honest_win_rate = honest.wins as f64 / blocks as f64;
+        let withholding_win_rate = withholding.wins as f64 / blocks as f64;
+
+        writer
+            .serialize(Line {
+                power,
+                blocks,
+                honest_win_rate,
+                withholding_win_rate,
+                gain: withholding_win_rate - honest_win_rate,
+                decision_gain: withholding.decision_gain as f64 / blocks as f64,
+                rerolls: withholding.rerolls,
+                withheld_blocks: withholding.withheld,
+            })
+            .unwrap();
+        writer.flush().unwrap();
+    }
+
+    progress.finish();
+}
+
+/// Simulate a chain of `EPOCHS` epochs, the attacker withholding its last
+/// blocks of an epoch if `withholds` and if it gives it more blocks.
+fn simulate(
+    honest_powers: &[Float],
+    attacker_power: &Float,
+    formula: WeightFormula,
+    withholds: bool,
+    progress: &ProgressBar,
+) -> Outcome {
+    let attacker = honest_powers.len() as u64;
+    let mut outcome = Outcome::default();
+
+    let mut last_hash = [0u8; 32];
+    let mut draws = epoch_draws(&seed(0), 0, honest_powers, attacker_power, formula);
+
+    for epoch in 0..EPOCHS {
+        let attacker_tail = draws.iter().rev().take_while(|(_, a)| *a).count();
+        let choices = if withholds { attacker_tail } else { 0 };
+
+        let mut best: Option<Choice> = None;
+        let mut publish_score = 0;
+
+        for withheld in 0..=choices {
+            let mut hash = last_hash;
+
+            for (i, (honest, attacker_wins)) in draws.iter().enumerate() {
+                let validator = if *attacker_wins && i < draws.len() - withheld {
+                    attacker
+                } else {
+                    *honest
+                };
+                let height = epoch * HEIGHTS_PER_EPOCH + i as u64;
+
+                hash = block_hash(&hash, height, 0, validator);
+            }
+
+            let next_draws = epoch_draws(
+                &next_seed(&hash),
+                epoch + 1,
+                honest_powers,
+                attacker_power,
+                formula,
+            );
+            let score = next_draws.iter().filter(|(_, a)| *a).count() as i64 - withheld as i64;
+
+            if withheld == 0 {
+                publish_score = score;
+            }
+
+            if best.as_ref().is_none_or(|b| score > b.score) {
+                best = Some(Choice {
+                    score,
+                    withheld,
+                    hash,
+                    next_draws,
+                });
+            }
+        }
+
+        let Choice {
+            score,
+            withheld,
+            hash,
+            next_draws,
+        } = best.unwrap();
+        outcome.decision_gain += score - publish_score;
+
+        outcome.wins += (draws.iter().filter(|(_, a)| *a).count() - withheld) as u64;
+        if withheld > 0 {
+            outcome.rerolls += 1;
+            outcome.withheld += withheld as u64;
+        }
+
+        last_hash = hash;
+        draws = next_draws;
+        progress.inc(1);
+    }
+
+    outcome
+}
+
+/// Best honest validator of each height of an epoch and whether the attacker
+/// beats it.
+fn epoch_draws(
+    seed: &[u8],
+    epoch: u64,
+    honest_powers: &[Float],
+    attacker_power: &Float,
+    formula: WeightFormula,
+) -> Vec<(u64, bool)> {
+    let attacker = honest_powers.len() as u64;
+
+    (epoch * HEIGHTS_PER_EPOCH..(epoch + 1) * HEIGHTS_PER_EPOCH)
+        .into_par_iter()
+        .map(|height| {
+            let mut best = 0;
+            let mut best_weight = Float::with_val(FLOAT_PRECISION, Special::NegInfinity);
+
+            for (validator, power) in honest_powers.iter().enumerate() {
+                let weight = formula(seed, power, height, 0, validator as u64, FLOAT_PRECISION);
+
+                if weight > best_weight {
+                    best = validator as u64;
+                    best_weight = weight;
+                }
+            }
+
+            let attacker_weight =
+                formula(seed, attacker_power, height, 0, attacker, FLOAT_PRECISION);
+
+            (best, attacker_weight > best_weight)
+        })
+        .collect()
+}
@@ -1,15 +1,45 @@
-use racoon_weight::weight_exp;
+// Cargo run --release --bin selfish -- [--output selfish.csv]
+
+use racoon_weight::{report, weight_exp};
 use rayon::prelude::*;
-use rug::Float; use rug::ops::Pow;
+use rug::Float;
+use serde::Serialize;
+use std::path::PathBuf;
+use structopt::StructOpt;
+
+/// Command line options.
+#[derive(Debug, StructOpt)]
+struct Options {
+    /// File where results are written (.json or .csv).
+    #[structopt(long, default_value = "selfish.csv")]
+    output: PathBuf,
+}
+
+/// Line of the output.
+#[derive(Serialize)]
+struct Line {
+    #[serde(rename = "Stake")]
+    stake: f64,
+    #[serde(rename = "Takeover Rate")]
+    take_over_rate: f64,
+}
 
 fn main() {
-    println!("Stake,Takeover Rate");
+    let options = Options::from_args();
+    let mut lines = vec![];
+
     for i in 0..60 {
         let power = i as f64 * 0.01;
         let take_over_rate = compute_selfish_rate(power);
         // let second_rate = compute_selfish_rate(power, false);
-        println!("{},{}", power, take_over_rate,);
+        lines.push(Line {
+            stake: power,
+            take_over_rate,
+        });
     }
+
+    report::write_lines(&options.output, &lines);
+    println!("Report written to {}", options.output.display());
 }
 
 fn compute_selfish_rate(attacker_power: f64) -> f64 {
@@ -0,0 +1,191 @@
+// Cargo run --release --bin sybil -- [--formula exp|log|fixed]
+//
+// Compare the win rate of one validator with power p against k validators with
+// power p/k each. A positive difference means splitting the stake is
+// rewarded, a negative one means pooling it is rewarded.
+//
+// Both runs use the same seeds and honest validators, so their blocks are
+// paired. The significance of the difference is given by McNemar's test on
+// the blocks won by the entity in only one of the runs.
+
+use racoon_weight::{powers, seed, weight_formula, Config, StakeDistribution, WeightFormula};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use rug::Float;
+use structopt::StructOpt;
+
+/// Amount of honest validators.
+const VALIDATORS: usize = 100;
+/// Number of shards.
+const SHARDS: u64 = 1;
+/// Number of epochs, each one having its own seed.
+const EPOCHS: u64 = 20;
+/// Number of blocks in 1 epoch.
+const HEIGHTS_PER_EPOCH: u64 = 500;
+/// Precisions in bits of the floating point numbers.
+const FLOAT_PRECISION: u32 = 53;
+/// Distribution of honest validators stakes.
+const STAKE_DISTRIBUTION: StakeDistribution = StakeDistribution::Hash { spread_factor: 20 };
+/// Total power of the tested entity.
+const ENTITY_POWERS: &[f64] = &[0.01, 0.1, 0.3];
+/// Amount of validators the entity splits its stake into.
+const SPLITS: &[usize] = &[2, 4, 8, 16];
+/// Two-sided critical value of McNemar's test (5% significance level).
+const Z_CRITICAL: f64 = 1.96;
+
+/// Command line options.
+#[derive(Debug, StructOpt)]
+struct Options {
+    /// Weight formula (exp, log or fixed).
+    #[structopt(long, default_value = "exp")]
+    formula: String,
+}
+
+fn main() {
+    let formula_name = Options::from_args().formula;
+    let formula = weight_formula(&formula_name)
+        .unwrap_or_else(|| panic!("unknown weight formula {:?}", formula_name));
+
+    println!("Weight formula: {}", formula_name);
+    println!("Honest validators: {}", VALIDATORS);
+    println!("Shards: {}", SHARDS);
+    println!("Epochs: {}", EPOCHS);
+    println!("Blocks per epoch: {}", HEIGHTS_PER_EPOCH);
+    println!("Float precision: {}", FLOAT_PRECISION);
+    println!("Stake distribution: {:?}", STAKE_DISTRIBUTION);
+    println!();
+
+    let honest_powers = powers(&STAKE_DISTRIBUTION, VALIDATORS, FLOAT_PRECISION);
+
+    // One simulation with a single entity validator, then one per split.
+    let simulations = ENTITY_POWERS.len() as u64 * (1 + SPLITS.len() as u64);
+    let progress = ProgressBar::new(simulations * EPOCHS * HEIGHTS_PER_EPOCH);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Simulating POS: [{elapsed} - {eta}] [{wide_bar}] Height {pos}/{len}")
+            .progress_chars("=> "),
+    );
+
+    let mut lines = vec![];
+
+    for &entity_power in ENTITY_POWERS {
+        let single = entity_blocks(&honest_powers, entity_power, 1, formula, &progress);
+
+        for &split in SPLITS {
+            let splitted = entity_blocks(&honest_powers, entity_power, split, formula, &progress);
+
+            // blocks won by the entity in only one of the runs
+            let single_only = single.iter().zip(&splitted).filter(|(a, b)| **a && !**b);
+            let split_only = single.iter().zip(&splitted).filter(|(a, b)| !**a && **b);
+
+            lines.push((
+                entity_power,
+                split,
+                single.iter().filter(|w| **w).count() as u64,
+                splitted.iter().filter(|w| **w).count() as u64,
+                single_only.count() as u64,
+                split_only.count() as u64,
+            ));
+        }
+    }
+
+    progress.finish();
+    println!();
+
+    let rounds = SHARDS * EPOCHS * HEIGHTS_PER_EPOCH;
+
+    println!("blocks per simulation: {}", rounds);
+    println!(
+        "power   split   single rate    split rate    diff        discordant        z  verdict"
+    );
+
+    for (entity_power, split, single, splitted, single_only, split_only) in lines {
+        let single_rate = single as f64 / rounds as f64;
+        let split_rate = splitted as f64 / rounds as f64;
+        let z = mcnemar_z(single_only, split_only);
+
+        let verdict = if z > Z_CRITICAL {
+            "splitting advantage"
+        } else if z < -Z_CRITICAL {
+            "pooling advantage"
+        } else {
+            "-"
+        };
+
+        println!(
+            "{:0.2} {:>8}    {:0.8}    {:0.8}    {:+0.8} {:>10} {:>+8.2}  {}",
+            entity_power,
+            split,
+            single_rate,
+            split_rate,
+            split_rate - single_rate,
+            single_only + split_only,
+            z,
+            verdict
+        );
+    }
+}
+
+/// Blocks won by an entity of given power splitted into `split` validators of
+/// equal power, in epoch, height and shard order. Honest validators share the
+/// remaining power.
+fn entity_blocks(
+    honest_powers: &[Float],
+    entity_power: f64,
+    split: usize,
+    formula: WeightFormula,
+    progress: &ProgressBar,
+) -> Vec<bool> {
+    let honest_share = Float::with_val(FLOAT_PRECISION, 1.0 - entity_power);
+    let entity_share = Float::with_val(FLOAT_PRECISION, entity_power) / split as u32;
+
+    // Honest validators come first so they keep the same ids, and thus the
+    // same random numbers, in every simulation.
+    let mut powers: Vec<_> = honest_powers
+        .iter()
+        .map(|p| Float::with_val(FLOAT_PRECISION, p * &honest_share))
+        .collect();
+    powers.extend(std::iter::repeat_n(entity_share, split));
+
+    let config = Config {
+        powers: &powers,
+        weight: &formula,
+        progress: || (),
+        validators: powers.len(),
+        shards: SHARDS,
+        epochs: EPOCHS,
+        blocks_per_epoch: HEIGHTS_PER_EPOCH,
+        precision: FLOAT_PRECISION,
+    };
+
+    let heights: Vec<_> = (0..EPOCHS)
+        .flat_map(|epoch| (0..HEIGHTS_PER_EPOCH).map(move |height| (epoch, height)))
+        .collect();
+
+    heights
+        .into_par_iter()
+        .flat_map_iter(|(epoch, height)| {
+            let seed = seed(epoch);
+            let wins: Vec<_> = (0..SHARDS)
+                .map(|shard| config.winner(&seed, height, shard).0 >= honest_powers.len())
+                .collect();
+
+            progress.inc(1);
+            wins
+        })
+        .collect()
+}
+
+/// McNemar's test statistic (normal approximation) of paired runs, given the
+/// blocks won by the entity only in the first run and only in the second one.
+/// Blocks won or lost in both runs carry no information on the difference.
+fn mcnemar_z(first_only: u64, second_only: u64) -> f64 {
+    let discordant = first_only + second_only;
+
+    if discordant == 0 {
+        return 0.0;
+    }
+
+    (second_only as f64 - first_only as f64) / (discordant as f64).sqrt()
+}
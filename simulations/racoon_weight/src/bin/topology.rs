@@ -0,0 +1,212 @@
+// Cargo run --release --bin topology -- [--latencies latencies.csv] > topology.csv
+//
+// Compare shard topologies by routing a cross-shard message between every
+// pair of shards along the shortest path. At each hop the message waits for
+// its block to be finalized on the current shard, then for the header to be
+// included and finalized on the next shard.
+//
+// The delay of each hop is the one of a random block of a racoon_weight3 run
+// with cross-links. The default config.ron of racoon_weight3 has none : set
+// `shards` above 1, `crosslink_period: Some(4)` and
+// `latencies_output: Some("latencies.csv")`, then run it from its directory
+// to write the default input of this simulation.
+// The latency of a pair is the sum of the delays of its hops, drawn for
+// `SAMPLED_PAIRS` random pairs. Its mean and 99th percentile are both taken
+// over these samples.
+//
+// Outputs the hop count and end-to-end latency of each topology as CSV.
+
+use racoon_core::{rng, topology::Topology};
+
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use structopt::StructOpt;
+
+/// Shard counts to test.
+const SHARD_COUNTS: &[usize] = &[16, 64, 256, 1024, 4096];
+/// Topologies to test.
+const TOPOLOGIES: &[Topology] = &[
+    Topology::Tree { arity: 2 },
+    Topology::Tree { arity: 4 },
+    Topology::Tree { arity: 8 },
+    Topology::Grid,
+    Topology::RandomRegular { degree: 4 },
+    Topology::SmallWorld {
+        degree: 4,
+        rewiring: 0.1,
+    },
+];
+/// Amount of (source, destination) pairs whose latency is drawn.
+const SAMPLED_PAIRS: u64 = 20_000;
+
+/// Command line options.
+#[derive(Debug, StructOpt)]
+struct Options {
+    /// Block latencies written by racoon_weight3.
+    #[structopt(long, default_value = "../racoon_weight3/latencies.csv")]
+    latencies: PathBuf,
+}
+
+/// Latencies of a finalized block, in ticks, as written by racoon_weight3.
+#[derive(Deserialize)]
+struct BlockLatency {
+    /// From the creation of the block to its finalization.
+    finality: u64,
+    /// From the finalization of the block to the finalization of the first
+    /// beacon block cross-linking it.
+    crosslink: Option<u64>,
+}
+
+/// Line of the CSV output.
+#[derive(Serialize)]
+struct Line {
+    topology: String,
+    shards: usize,
+    links: usize,
+    unreachable_pairs: u64,
+    mean_hops: f64,
+    p99_hops: usize,
+    max_hops: usize,
+    mean_latency: f64,
+    p99_latency: f64,
+}
+
+fn main() {
+    let options = Options::from_args();
+    let hop_latencies = hop_latencies(&options.latencies);
+
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+
+    for topology in TOPOLOGIES {
+        for &shards in SHARD_COUNTS {
+            let graph = topology.graph(shards);
+
+            // histogram[h] : amount of (source, destination) pairs at h hops
+            let (histogram, unreachable_pairs) = (0..shards)
+                .into_par_iter()
+                .map(|source| {
+                    let mut histogram = vec![0u64; shards];
+                    let mut unreachable = 0;
+
+                    for (destination, hops) in graph.hops_from(source).into_iter().enumerate() {
+                        match hops {
+                            _ if destination == source => (),
+                            Some(hops) => histogram[hops as usize] += 1,
+                            None => unreachable += 1,
+                        }
+                    }
+
+                    (histogram, unreachable)
+                })
+                .reduce(
+                    || (vec![0u64; shards], 0),
+                    |(mut a, unreachable_a), (b, unreachable_b)| {
+                        a.iter_mut().zip(b).for_each(|(a, b)| *a += b);
+                        (a, unreachable_a + unreachable_b)
+                    },
+                );
+
+            let pairs: u64 = histogram.iter().sum();
+            let mean_hops = histogram
+                .iter()
+                .enumerate()
+                .map(|(hops, count)| hops as u64 * count)
+                .sum::<u64>() as f64
+                / pairs as f64;
+            let p99_hops = percentile(&histogram, 0.99);
+            let max_hops = histogram.iter().rposition(|&count| count > 0).unwrap();
+
+            let mut latencies: Vec<_> = (0..SAMPLED_PAIRS)
+                .into_par_iter()
+                .map(|pair| {
+                    let values = [shards as u64, pair];
+                    let rank = (rng::uniform(b"pair", &values) * pairs as f64) as u64;
+                    let hops = hops_at(&histogram, rank);
+
+                    (0..hops as u64)
+                        .map(|hop| {
+                            let u = rng::uniform(b"hop", &[shards as u64, pair, hop]);
+                            hop_latencies[(u * hop_latencies.len() as f64) as usize]
+                        })
+                        .sum::<f64>()
+                })
+                .collect();
+            latencies.sort_by(|a, b| a.partial_cmp(b).unwrap());
+            let mean_latency = latencies.iter().sum::<f64>() / latencies.len() as f64;
+            let p99_latency = latencies[((latencies.len() as f64 * 0.99).ceil() as usize) - 1];
+
+            writer
+                .serialize(Line {
+                    topology: format!("{:?}", topology),
+                    shards,
+                    links: graph.links(),
+                    unreachable_pairs,
+                    mean_hops,
+                    p99_hops,
+                    max_hops,
+                    mean_latency,
+                    p99_latency,
+                })
+                .unwrap();
+        }
+
+        writer.flush().unwrap();
+    }
+}
+
+/// Delay of a hop through each block cross-linked in a racoon_weight3 run.
+fn hop_latencies(path: &Path) -> Vec<f64> {
+    let mut reader = csv::Reader::from_path(path).unwrap_or_else(|e| {
+        panic!(
+            "cannot read {}: {}, run racoon_weight3 with a crosslink_period and latencies_output",
+            path.display(),
+            e
+        )
+    });
+
+    let latencies: Vec<_> = reader
+        .deserialize()
+        .map(|line| line.unwrap_or_else(|e| panic!("cannot read {}: {}", path.display(), e)))
+        .filter_map(|line: BlockLatency| Some((line.finality + line.crosslink?) as f64))
+        .collect();
+
+    assert!(
+        !latencies.is_empty(),
+        "no cross-linked block in {}, run racoon_weight3 with a crosslink_period",
+        path.display()
+    );
+
+    latencies
+}
+
+/// Smallest hop count reached by given fraction of the pairs.
+fn percentile(histogram: &[u64], fraction: f64) -> usize {
+    let pairs: u64 = histogram.iter().sum();
+    let mut cumulated = 0;
+
+    for (hops, count) in histogram.iter().enumerate() {
+        cumulated += count;
+
+        if cumulated as f64 >= fraction * pairs as f64 {
+            return hops;
+        }
+    }
+
+    histogram.len() - 1
+}
+
+/// Hop count of the pair of given rank, pairs being sorted by hop count.
+fn hops_at(histogram: &[u64], rank: u64) -> usize {
+    let mut cumulated = 0;
+
+    for (hops, count) in histogram.iter().enumerate() {
+        cumulated += count;
+
+        if rank < cumulated {
+            return hops;
+        }
+    }
+
+    histogram.len() - 1
+}
@@ -0,0 +1,149 @@
+// Cargo run --release --bin withholding -- [--formula exp|log|fixed] > withholding.csv
+//
+// An attacker with power p loses a block fairly, then withholds its own fork
+// for up to k blocks. It takes over the chain if at some point its fork
+// cumulative weight is higher than the honest fork one, the same way
+// racoon_weight3 compares forks. The honest side is made of many validators,
+// the best of them creating each honest block.
+//
+// Outputs the takeover probability as CSV.
+
+use racoon_weight::{powers, seed, weight_formula, Config, StakeDistribution, WeightFormula};
+
+use indicatif::{ProgressBar, ProgressStyle};
+use rayon::prelude::*;
+use rug::Float;
+use serde::Serialize;
+use structopt::StructOpt;
+
+/// Amount of honest validators.
+const VALIDATORS: usize = 100;
+/// Number of fork attempts for each attacker power.
+const TRIES: u64 = 1000;
+/// Maximum amount of blocks the attacker withholds.
+const MAX_WITHHELD: u64 = 8;
+/// Precisions in bits of the floating point numbers.
+const FLOAT_PRECISION: u32 = 53;
+/// Distribution of honest validators stakes.
+const STAKE_DISTRIBUTION: StakeDistribution = StakeDistribution::Hash { spread_factor: 20 };
+/// Attacker powers to test.
+const ATTACKER_POWERS: &[f64] = &[0.0, 0.05, 0.1, 0.15, 0.2, 0.25, 0.3, 0.35, 0.4, 0.45, 0.5];
+
+/// Line of the CSV output.
+#[derive(Serialize)]
+struct Line {
+    power: f64,
+    withheld: u64,
+    attempts: usize,
+    takeover_rate: f64,
+}
+
+/// Command line options.
+#[derive(Debug, StructOpt)]
+struct Options {
+    /// Weight formula (exp, log or fixed).
+    #[structopt(long, default_value = "exp")]
+    formula: String,
+}
+
+fn main() {
+    let formula_name = Options::from_args().formula;
+    let formula = weight_formula(&formula_name)
+        .unwrap_or_else(|| panic!("unknown weight formula {:?}", formula_name));
+
+    let honest_powers = powers(&STAKE_DISTRIBUTION, VALIDATORS, FLOAT_PRECISION);
+
+    let progress = ProgressBar::new(ATTACKER_POWERS.len() as u64 * TRIES);
+    progress.set_style(
+        ProgressStyle::default_bar()
+            .template("Simulating forks: [{elapsed} - {eta}] [{wide_bar}] Try {pos}/{len}")
+            .progress_chars("=> "),
+    );
+
+    let mut writer = csv::Writer::from_writer(std::io::stdout());
+
+    for &power in ATTACKER_POWERS {
+        // Honest validators share the remaining power.
+        let honest_share = Float::with_val(FLOAT_PRECISION, 1.0 - power);
+        let honest_powers: Vec<_> = honest_powers
+            .iter()
+            .map(|p| Float::with_val(FLOAT_PRECISION, p * &honest_share))
+            .collect();
+
+        let takeovers: Vec<_> = (0..TRIES)
+            .into_par_iter()
+            .filter_map(|i| {
+                let takeover = takeover_height(&honest_powers, power, formula, &seed(i));
+                progress.inc(1);
+                takeover
+            })
+            .collect();
+
+        for withheld in 1..=MAX_WITHHELD {
+            let attempts = takeovers.len();
+            let successes = takeovers
+                .iter()
+                .filter(|t| matches!(t, Some(h) if *h <= withheld))
+                .count();
+
+            writer
+                .serialize(Line {
+                    power,
+                    withheld,
+                    attempts,
+                    takeover_rate: successes as f64 / attempts as f64,
+                })
+                .unwrap();
+        }
+
+        writer.flush().unwrap();
+    }
+
+    progress.finish();
+}
+
+/// Simulate a fork for given seed.
+/// Returns `None` if the attacker fairly wins the first block (no attack),
+/// otherwise the amount of withheld blocks after which the attacker fork
+/// becomes heavier than the honest one, if it happens within `MAX_WITHHELD`.
+fn takeover_height(
+    honest_powers: &[Float],
+    attacker_power: f64,
+    formula: WeightFormula,
+    seed: &[u8],
+) -> Option<Option<u64>> {
+    let attacker_power = Float::with_val(FLOAT_PRECISION, attacker_power);
+    let attacker = honest_powers.len() as u64;
+
+    let honest = Config {
+        powers: honest_powers,
+        weight: &formula,
+        progress: || (),
+        validators: honest_powers.len(),
+        shards: 1,
+        epochs: 1,
+        blocks_per_epoch: MAX_WITHHELD,
+        precision: FLOAT_PRECISION,
+    };
+
+    let mut honest_fork_weight = Float::with_val(FLOAT_PRECISION, 0);
+    let mut attacker_fork_weight = Float::with_val(FLOAT_PRECISION, 0);
+
+    for height in 0..MAX_WITHHELD {
+        let (_, honest_weight) = honest.winner(seed, height, 0);
+        let attacker_weight = formula(seed, &attacker_power, height, 0, attacker, FLOAT_PRECISION);
+
+        if height == 0 && attacker_weight > honest_weight {
+            return None; // attacker fairly wins, ignoring
+        }
+
+        honest_fork_weight += honest_weight;
+        attacker_fork_weight += attacker_weight;
+
+        if attacker_fork_weight > honest_fork_weight {
+            return Some(Some(height + 1));
+        }
+    }
+
+    Some(None)
+}
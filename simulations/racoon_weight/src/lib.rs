@@ -1,7 +1,37 @@
+pub mod report;
+pub mod stats;
+
 use rayon::prelude::*;
-use rug::{float::Special, integer::Order, ops::Pow, Float, Integer};
+use rug::{float::Special, Float};
 use sha3::{Digest, Sha3_256};
 
+pub use racoon_core::{
+    fixed::{self, weight_fixed},
+    stake::{powers, stake, StakeDistribution},
+    weight::{random, weight_exp, weight_log},
+};
+
+/// Weight formula (seed, power, height, shard, validator, precision).
+pub type WeightFormula = fn(&[u8], &Float, u64, u64, u64, u32) -> Float;
+
+/// Available weight formulas with their names.
+pub const WEIGHT_FORMULAS: &[(&str, WeightFormula)] = &[
+    ("exp", weight_exp),
+    ("log", weight_log),
+    ("fixed", weight_fixed),
+];
+
+/// Find a weight formula by its name.
+pub fn weight_formula(name: &str) -> Option<WeightFormula> {
+    WEIGHT_FORMULAS
+        .iter()
+        .find(|(n, _)| *n == name)
+        .map(|(_, formula)| *formula)
+}
+
+/// Significance level of the confidence intervals shown by `Result::display`.
+pub const DISPLAY_SIGNIFICANCE: f64 = 0.05;
+
 /// Result of a simulation.
 pub struct Result {
     /// Amount of simulated rounds.
@@ -49,6 +79,38 @@ impl Result {
         a
     }
 
+    /// Binomial confidence interval of each validator win rate at given
+    /// significance level.
+    pub fn confidence_intervals(&self, significance: f64) -> Vec<(f64, f64)> {
+        self.wins
+            .iter()
+            .map(|&w| stats::wilson_interval(w, self.rounds, significance))
+            .collect()
+    }
+
+    /// Chi-squared goodness-of-fit test of the wins against validators powers.
+    /// Returns the statistic, the degrees of freedom and the p-value.
+    pub fn chi_squared(&self, powers: &[Float]) -> (f64, u64, f64) {
+        stats::chi_squared(&self.wins, &to_f64(powers))
+    }
+
+    /// Kullback-Leibler divergence of the win rates from validators powers.
+    pub fn kl_divergence(&self, powers: &[Float]) -> f64 {
+        stats::kl_divergence(&self.wins, &to_f64(powers))
+    }
+
+    /// Check that the wins are consistent with validators powers, ie. that the
+    /// chi-squared test doesn't reject the formula at given significance level.
+    pub fn is_fair(&self, powers: &[Float], significance: f64) -> bool {
+        let (_, _, p_value) = self.chi_squared(powers);
+        p_value >= significance
+    }
+
+    /// Machine readable version of the results.
+    pub fn report(&self, powers: &[Float], precision: u32) -> report::Report {
+        report::Report::new(self, powers, precision)
+    }
+
     /// Display the results in a human readable format.
     pub fn display(&self, powers: &[Float], top_amount: usize, precision: u32) {
         let win_rates: Vec<_> = self
@@ -57,17 +119,32 @@ impl Result {
             .map(|w| Float::with_val(precision, w) / Float::with_val(precision, self.rounds))
             .collect();
 
+        let intervals = self.confidence_intervals(DISPLAY_SIGNIFICANCE);
+
         println!("Results (top {} validators) :", top_amount);
-        println!("power         win rate       wins    diff");
+        println!(
+            "power         win rate       wins    diff           {}% interval",
+            (1.0 - DISPLAY_SIGNIFICANCE) * 100.0
+        );
 
         for i in 0..top_amount {
             let diff = Float::with_val(precision, &win_rates[i] - &powers[i]);
+            let (low, high) = intervals[i];
+            let power = powers[i].to_f64();
+            let outside = if power < low || power > high {
+                " *"
+            } else {
+                ""
+            };
             println!(
-                "{:0.8}    {:0.8} {:>8}    {:+0.8}",
-                powers[i].to_f64(),
+                "{:0.8}    {:0.8} {:>8}    {:+0.8}    [{:0.8}; {:0.8}]{}",
+                power,
                 win_rates[i].to_f64(),
                 self.wins[i],
-                diff.to_f64()
+                diff.to_f64(),
+                low,
+                high,
+                outside
             );
         }
 
@@ -84,9 +161,21 @@ impl Result {
             )
             .to_f64()
         );
+
+        let (statistic, degrees, p_value) = self.chi_squared(powers);
+        println!(
+            "chi-squared : {:0.4} ({} degrees of freedom, p-value {:0.6})",
+            statistic, degrees, p_value
+        );
+        println!("KL divergence : {:e}", self.kl_divergence(powers));
     }
 }
 
+/// Convert powers to `f64` for statistical tests.
+fn to_f64(powers: &[Float]) -> Vec<f64> {
+    powers.iter().map(Float::to_f64).collect()
+}
+
 /// Configuration of the simulation.
 pub struct Config<'a, W, P>
 where
@@ -119,26 +208,33 @@ where
     W: Sync + Fn(&[u8], &Float, u64, u64, u64, u32) -> Float,
     P: Sync + Fn(),
 {
+    /// Find the winner of a block and its weight.
+    pub fn winner(&self, seed: &[u8], height: u64, shard: u64) -> (usize, Float) {
+        let mut winner = 0;
+        let mut winner_weight = Float::with_val(self.precision, Special::NegInfinity);
+
+        for (validator, power) in self.powers.iter().enumerate() {
+            let weight =
+                (self.weight)(seed, power, height, shard, validator as u64, self.precision);
+
+            // println!("{}", weight);
+
+            if weight > winner_weight {
+                winner = validator;
+                winner_weight = weight;
+            }
+        }
+
+        (winner, winner_weight)
+    }
+
     /// Simulate the POS algorithm on all shards for the same height.
     pub fn simulate_height(&self, seed: &[u8], height: u64) -> Result {
         let mut shards_wins = vec![0; self.validators];
         let mut result = Result::new(self.validators, self.precision);
 
         for shard in 0..self.shards {
-            let mut winner = 0;
-            let mut winner_weight = Float::with_val(self.precision, Special::NegInfinity);
-
-            for (validator, power) in self.powers.iter().enumerate() {
-                let weight =
-                    (self.weight)(seed, power, height, shard, validator as u64, self.precision);
-
-                // println!("{}", weight);
-
-                if weight > winner_weight {
-                    winner = validator;
-                    winner_weight = weight;
-                }
-            }
+            let (winner, winner_weight) = self.winner(seed, height, shard);
 
             // println!("{},{}", winner, &winner_weight);
 
@@ -159,16 +255,9 @@ where
         result
     }
 
-    fn seed(epoch: u64) -> [u8; 32] {
-        let mut hasher = Sha3_256::new();
-        hasher.input(b"seed");
-        hasher.input(epoch.to_be_bytes());
-        hasher.result().into()
-    }
-
     /// Simulate the POS algorithm on all shards for all blocks in given epoch.
     pub fn simulate_epoch(&self, epoch: u64) -> Result {
-        let seed = Self::seed(epoch);
+        let seed = seed(epoch);
 
         (0..self.blocks_per_epoch)
             .into_par_iter()
@@ -192,54 +281,30 @@ where
     }
 }
 
-/// Compute a "random number".
-pub fn random(seed: &[u8], height: u64, shard: u64, validator: u64, precision: u32) -> Float {
-    // Generate "random" number.
+/// Seed used for all blocks of an epoch.
+pub fn seed(epoch: u64) -> [u8; 32] {
     let mut hasher = Sha3_256::new();
-    hasher.input(seed);
-    hasher.input(shard.to_be_bytes());
-    hasher.input(height.to_be_bytes());
-    hasher.input(validator.to_be_bytes());
-    let hash = hasher.result();
-
-    let hash = Integer::from_digits(&hash, Order::Lsf);
-    Float::with_val(precision, hash)
+    hasher.input(b"seed");
+    hasher.input(epoch.to_be_bytes());
+    hasher.result().into()
 }
 
-/// Weight forumula using a single exp.
-pub fn weight_exp(
-    seed: &[u8],
-    power: &Float,
-    height: u64,
-    shard: u64,
-    validator: u64,
-    precision: u32,
-) -> Float {
-    let rand = random(seed, height, shard, validator, precision);
-
-    // Transform number in interval [0;1].
-    let hash_max = Float::with_val(precision, 2).pow(256);
-    let rand: Float = rand / hash_max;
-
-    // Compute weight.
-    rand.pow(Float::with_val(precision, 1 / power))
+/// Hash of a block, chaining the hash of its previous block.
+pub fn block_hash(previous_hash: &[u8], height: u64, shard: u64, validator: u64) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.input(b"block");
+    hasher.input(previous_hash);
+    hasher.input(height.to_be_bytes());
+    hasher.input(shard.to_be_bytes());
+    hasher.input(validator.to_be_bytes());
+    hasher.result().into()
 }
 
-pub fn weight_log(
-    seed: &[u8],
-    power: &Float,
-    height: u64,
-    shard: u64,
-    validator: u64,
-    precision: u32,
-) -> Float {
-    let rand = random(seed, height, shard, validator, precision);
-
-    // Compute weight.
-    let ln_r = rand.ln();
-    let hash_max: Float = Float::with_val(precision, 2).pow(256);
-    let ln_max = hash_max.clone().ln();
-    let ln_d = Float::with_val(precision, 5).ln();
-
-    (ln_r - ln_max) / (power * ln_d) // + hash_max
+/// Seed of the next epoch derived from the hash of the last finalized block
+/// of an epoch. Unlike `seed`, the last proposers can influence it.
+pub fn next_seed(last_block_hash: &[u8]) -> [u8; 32] {
+    let mut hasher = Sha3_256::new();
+    hasher.input(b"seed");
+    hasher.input(last_block_hash);
+    hasher.result().into()
 }
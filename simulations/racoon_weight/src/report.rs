@@ -0,0 +1,138 @@
+//! Machine readable simulation results.
+
+use crate::Result;
+
+use rug::Float;
+use serde::Serialize;
+use std::{fs::File, path::Path};
+
+/// Serializable summary of a simulation `Result`.
+#[derive(Clone, Debug, Serialize)]
+pub struct Report {
+    /// Amount of simulated rounds.
+    pub rounds: u64,
+    /// Power of each validator.
+    pub powers: Vec<f64>,
+    /// Number of wins for each validator.
+    pub wins: Vec<u64>,
+    /// Lowest weight winning a block.
+    pub min_win_weight: f64,
+    /// Highest weight winning a block.
+    pub max_win_weight: f64,
+    /// Average weight winning a block.
+    pub avg_win_weight: f64,
+    /// Maximum of shards on which the same validator won at the same height.
+    pub max_win_across_shards: u64,
+}
+
+/// Line of a CSV report : one per validator, with the global values repeated
+/// so each line can be used on its own.
+#[derive(Serialize)]
+struct CsvLine {
+    validator: usize,
+    power: f64,
+    wins: u64,
+    win_rate: f64,
+    rounds: u64,
+    min_win_weight: f64,
+    max_win_weight: f64,
+    avg_win_weight: f64,
+    max_win_across_shards: u64,
+}
+
+impl Report {
+    /// Build the report of a result.
+    pub fn new(result: &Result, powers: &[Float], precision: u32) -> Self {
+        let avg_win_weight = Float::with_val(
+            precision,
+            &result.sum_win_weight / Float::with_val(precision, result.rounds),
+        );
+
+        Self {
+            rounds: result.rounds,
+            powers: powers.iter().map(Float::to_f64).collect(),
+            wins: result.wins.clone(),
+            min_win_weight: result.min_win_weight.to_f64(),
+            max_win_weight: result.max_win_weight.to_f64(),
+            avg_win_weight: avg_win_weight.to_f64(),
+            max_win_across_shards: result.max_win_across_shards,
+        }
+    }
+
+    /// Write the report as JSON.
+    pub fn write_json(&self, file: File) {
+        serde_json::to_writer_pretty(file, self).unwrap();
+    }
+
+    /// Write the report as CSV.
+    pub fn write_csv(&self, file: File) {
+        let mut writer = csv::Writer::from_writer(file);
+
+        for (validator, (power, wins)) in self.powers.iter().zip(self.wins.iter()).enumerate() {
+            writer
+                .serialize(CsvLine {
+                    validator,
+                    power: *power,
+                    wins: *wins,
+                    win_rate: *wins as f64 / self.rounds as f64,
+                    rounds: self.rounds,
+                    min_win_weight: self.min_win_weight,
+                    max_win_weight: self.max_win_weight,
+                    avg_win_weight: self.avg_win_weight,
+                    max_win_across_shards: self.max_win_across_shards,
+                })
+                .unwrap();
+        }
+
+        writer.flush().unwrap();
+    }
+
+    /// Write the report to a file, in JSON or CSV depending on its extension.
+    pub fn write<P: AsRef<Path>>(&self, path: P) {
+        let (file, format) = create(path.as_ref());
+
+        match format {
+            Format::Json => self.write_json(file),
+            Format::Csv => self.write_csv(file),
+        }
+    }
+}
+
+/// Format of a report file.
+enum Format {
+    Json,
+    Csv,
+}
+
+/// Create a report file, finding its format from its extension.
+fn create(path: &Path) -> (File, Format) {
+    let format = match path.extension().and_then(|e| e.to_str()) {
+        Some("json") => Format::Json,
+        Some("csv") => Format::Csv,
+        _ => panic!("unknown report format for {:?}, use .json or .csv", path),
+    };
+
+    let file =
+        File::create(path).unwrap_or_else(|e| panic!("cannot create {}: {}", path.display(), e));
+
+    (file, format)
+}
+
+/// Write lines of results to a file, as a JSON array or one CSV line each
+/// depending on its extension.
+pub fn write_lines<T: Serialize, P: AsRef<Path>>(path: P, lines: &[T]) {
+    let (file, format) = create(path.as_ref());
+
+    match format {
+        Format::Json => serde_json::to_writer_pretty(file, lines).unwrap(),
+        Format::Csv => {
+            let mut writer = csv::Writer::from_writer(file);
+
+            for line in lines {
+                writer.serialize(line).unwrap();
+            }
+
+            writer.flush().unwrap();
+        }
+    }
+}
@@ -0,0 +1,329 @@
+//! Statistical tools used to check the fairness of weight formulas.
+
+/// Wilson score interval of a binomial proportion with `successes` out of
+/// `trials`, for a two-sided significance level.
+pub fn wilson_interval(successes: u64, trials: u64, significance: f64) -> (f64, f64) {
+    if trials == 0 {
+        return (0.0, 1.0);
+    }
+
+    let z = normal_quantile(1.0 - significance / 2.0);
+    let n = trials as f64;
+    let p = successes as f64 / n;
+    let z2 = z * z;
+
+    let center = (p + z2 / (2.0 * n)) / (1.0 + z2 / n);
+    let margin = z / (1.0 + z2 / n) * (p * (1.0 - p) / n + z2 / (4.0 * n * n)).sqrt();
+
+    ((center - margin).max(0.0), (center + margin).min(1.0))
+}
+
+/// Chi-squared goodness-of-fit test of observed counts against expected
+/// probabilities.
+/// Categories with an expected count below 5 are pooled together, smallest
+/// first, so the chi-squared approximation holds.
+/// Returns the statistic, the degrees of freedom and the p-value.
+pub fn chi_squared(observed: &[u64], probabilities: &[f64]) -> (f64, u64, f64) {
+    let total: u64 = observed.iter().sum();
+
+    let mut bins: Vec<_> = observed
+        .iter()
+        .zip(probabilities.iter())
+        .map(|(&o, &p)| (o as f64, p * total as f64))
+        .collect();
+    bins.sort_by(|a, b| a.1.partial_cmp(&b.1).unwrap());
+
+    // pool smallest bins until they reach the minimum expected count
+    let mut pooled: Vec<(f64, f64)> = vec![];
+    let mut current = (0.0, 0.0);
+
+    for (o, e) in bins {
+        current.0 += o;
+        current.1 += e;
+
+        if current.1 >= 5.0 {
+            pooled.push(current);
+            current = (0.0, 0.0);
+        }
+    }
+
+    if current.1 > 0.0 {
+        match pooled.last_mut() {
+            Some(last) => {
+                last.0 += current.0;
+                last.1 += current.1;
+            }
+            None => pooled.push(current),
+        }
+    }
+
+    if pooled.len() < 2 {
+        return (0.0, 0, 1.0);
+    }
+
+    let statistic = pooled
+        .iter()
+        .map(|(o, e)| (o - e) * (o - e) / e)
+        .sum::<f64>();
+    let degrees = pooled.len() as u64 - 1;
+
+    (statistic, degrees, chi_squared_survival(statistic, degrees))
+}
+
+/// Kullback-Leibler divergence of observed frequencies from expected
+/// probabilities, in nats.
+pub fn kl_divergence(observed: &[u64], probabilities: &[f64]) -> f64 {
+    let total: u64 = observed.iter().sum();
+
+    if total == 0 {
+        return 0.0;
+    }
+
+    observed
+        .iter()
+        .zip(probabilities.iter())
+        .filter(|(&o, _)| o > 0)
+        .map(|(&o, &p)| {
+            let q = o as f64 / total as f64;
+            q * (q / p).ln()
+        })
+        .sum()
+}
+
+/// Probability for a chi-squared variable with `degrees` degrees of freedom to
+/// be greater than `x`.
+pub fn chi_squared_survival(x: f64, degrees: u64) -> f64 {
+    if x <= 0.0 {
+        return 1.0;
+    }
+
+    upper_regularized_gamma(degrees as f64 / 2.0, x / 2.0)
+}
+
+/// Upper regularized incomplete gamma function Q(a, x).
+fn upper_regularized_gamma(a: f64, x: f64) -> f64 {
+    const EPSILON: f64 = 1e-15;
+    const MAX_ITERATIONS: usize = 10_000;
+
+    let log_prefix = a * x.ln() - x - ln_gamma(a);
+
+    if x < a + 1.0 {
+        // series of P(a, x)
+        let mut term = 1.0 / a;
+        let mut sum = term;
+        let mut n = a;
+
+        for _ in 0..MAX_ITERATIONS {
+            n += 1.0;
+            term *= x / n;
+            sum += term;
+
+            if term.abs() < sum.abs() * EPSILON {
+                break;
+            }
+        }
+
+        1.0 - sum * log_prefix.exp()
+    } else {
+        // continued fraction of Q(a, x) (modified Lentz)
+        let tiny = 1e-300;
+        let mut b = x + 1.0 - a;
+        let mut c = 1.0 / tiny;
+        let mut d = 1.0 / b;
+        let mut h = d;
+
+        for i in 1..MAX_ITERATIONS {
+            let an = -(i as f64) * (i as f64 - a);
+            b += 2.0;
+
+            d = an * d + b;
+            if d.abs() < tiny {
+                d = tiny;
+            }
+            c = b + an / c;
+            if c.abs() < tiny {
+                c = tiny;
+            }
+            d = 1.0 / d;
+
+            let delta = d * c;
+            h *= delta;
+
+            if (delta - 1.0).abs() < EPSILON {
+                break;
+            }
+        }
+
+        h * log_prefix.exp()
+    }
+}
+
+/// Natural logarithm of the gamma function (Lanczos approximation).
+fn ln_gamma(x: f64) -> f64 {
+    const COEFFICIENTS: [f64; 6] = [
+        76.180_091_729_471_46,
+        -86.505_320_329_416_77,
+        24.014_098_240_830_91,
+        -1.231_739_572_450_155,
+        0.001_208_650_973_866_179,
+        -0.000_005_395_239_384_953,
+    ];
+
+    let tmp = x + 5.5 - (x + 0.5) * (x + 5.5).ln();
+    let mut series = 1.000_000_000_190_015;
+    let mut y = x;
+
+    for c in COEFFICIENTS.iter() {
+        y += 1.0;
+        series += c / y;
+    }
+
+    -tmp + (2.506_628_274_631_000_5 * series / x).ln()
+}
+
+/// Quantile function of the standard normal distribution (Acklam's
+/// approximation, relative error below 1.15e-9).
+pub fn normal_quantile(p: f64) -> f64 {
+    const A: [f64; 6] = [
+        -3.969_683_028_665_376e1,
+        2.209_460_984_245_205e2,
+        -2.759_285_104_469_687e2,
+        1.383_577_518_672_69e2,
+        -3.066_479_806_614_716e1,
+        2.506_628_277_459_239,
+    ];
+    const B: [f64; 5] = [
+        -5.447_609_879_822_406e1,
+        1.615_858_368_580_409e2,
+        -1.556_989_798_598_866e2,
+        6.680_131_188_771_972e1,
+        -1.328_068_155_288_572e1,
+    ];
+    const C: [f64; 6] = [
+        -7.784_894_002_430_293e-3,
+        -3.223_964_580_411_365e-1,
+        -2.400_758_277_161_838,
+        -2.549_732_539_343_734,
+        4.374_664_141_464_968,
+        2.938_163_982_698_783,
+    ];
+    const D: [f64; 4] = [
+        7.784_695_709_041_462e-3,
+        3.224_671_290_700_398e-1,
+        2.445_134_137_142_996,
+        3.754_408_661_907_416,
+    ];
+    const LOW: f64 = 0.024_25;
+
+    if p <= 0.0 {
+        return f64::NEG_INFINITY;
+    }
+    if p >= 1.0 {
+        return f64::INFINITY;
+    }
+
+    if p < LOW {
+        let q = (-2.0 * p.ln()).sqrt();
+        (((((C[0] * q + C[1]) * q + C[2]) * q + C[3]) * q + C[4]) * q + C[5])
+            / ((((D[0] * q + D[1]) * q + D[2]) * q + D[3]) * q + 1.0)
+    } else if p <= 1.0 - LOW {
+        let q = p - 0.5;
+        let r = q * q;
+        (((((A[0] * r + A[1]) * r + A[2]) * r + A[3]) * r + A[4]) * r + A[5]) * q
+            / (((((B[0] * r + B[1]) * r + B[2]) * r + B[3]) * r + B[4]) * r + 1.0)
+    } else {
+        -normal_quantile(1.0 - p)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(actual: f64, expected: f64, tolerance: f64) {
+        assert!(
+            (actual - expected).abs() < tolerance,
+            "{} is not {} (± {})",
+            actual,
+            expected,
+            tolerance
+        );
+    }
+
+    #[test]
+    fn chi_squared_critical_values() {
+        // 5% and 1% critical values from statistical tables
+        assert_close(chi_squared_survival(3.841, 1), 0.05, 1e-4);
+        assert_close(chi_squared_survival(5.991, 2), 0.05, 1e-4);
+        assert_close(chi_squared_survival(18.307, 10), 0.05, 1e-4);
+        assert_close(chi_squared_survival(6.635, 1), 0.01, 1e-4);
+        assert_close(chi_squared_survival(124.342, 100), 0.05, 1e-4);
+    }
+
+    #[test]
+    fn chi_squared_two_degrees_is_exponential() {
+        for x in &[0.1, 1.0, 4.0, 20.0] {
+            assert_close(chi_squared_survival(*x, 2), (-x / 2.0).exp(), 1e-12);
+        }
+
+        assert_eq!(chi_squared_survival(0.0, 3), 1.0);
+    }
+
+    #[test]
+    fn ln_gamma_known_values() {
+        assert_close(ln_gamma(1.0), 0.0, 1e-10);
+        assert_close(ln_gamma(5.0), 24f64.ln(), 1e-10);
+        assert_close(ln_gamma(0.5), std::f64::consts::PI.sqrt().ln(), 1e-10);
+        assert_close(ln_gamma(50.5), 146.5192554907, 1e-7);
+    }
+
+    #[test]
+    fn normal_quantile_known_values() {
+        assert_close(normal_quantile(0.5), 0.0, 1e-9);
+        assert_close(normal_quantile(0.975), 1.959_963_985, 1e-8);
+        assert_close(normal_quantile(0.01), -2.326_347_874, 1e-8);
+        assert_close(normal_quantile(0.999), 3.090_232_306, 1e-8);
+        assert_eq!(normal_quantile(0.0), f64::NEG_INFINITY);
+        assert_eq!(normal_quantile(1.0), f64::INFINITY);
+    }
+
+    #[test]
+    fn wilson_interval_known_values() {
+        let (low, high) = wilson_interval(50, 100, 0.05);
+        assert_close(low, 0.403_832, 1e-6);
+        assert_close(high, 0.596_168, 1e-6);
+
+        assert_eq!(wilson_interval(0, 0, 0.05), (0.0, 1.0));
+        assert_eq!(wilson_interval(0, 10, 0.05).0, 0.0);
+    }
+
+    #[test]
+    fn chi_squared_of_expected_counts() {
+        let (statistic, degrees, p_value) = chi_squared(&[250, 250, 500], &[0.25, 0.25, 0.5]);
+
+        assert_eq!(statistic, 0.0);
+        assert_eq!(degrees, 2);
+        assert_close(p_value, 1.0, 1e-12);
+    }
+
+    #[test]
+    fn chi_squared_pools_small_bins() {
+        // 10 bins expecting 1 are pooled 5 by 5, leaving 3 bins
+        let mut observed = vec![1; 10];
+        observed.push(90);
+        let mut probabilities = vec![0.01; 10];
+        probabilities.push(0.9);
+
+        let (statistic, degrees, _) = chi_squared(&observed, &probabilities);
+
+        assert_eq!(degrees, 2);
+        assert_close(statistic, 0.0, 1e-12);
+    }
+
+    #[test]
+    fn kl_divergence_known_values() {
+        assert_eq!(kl_divergence(&[25, 75], &[0.25, 0.75]), 0.0);
+        assert_close(kl_divergence(&[50, 50], &[0.25, 0.75]), 0.143_841, 1e-6);
+        assert_eq!(kl_divergence(&[0, 0], &[0.5, 0.5]), 0.0);
+    }
+}
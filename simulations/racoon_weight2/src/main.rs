@@ -1,20 +1,18 @@
-use rug::{integer::Order, ops::Pow, Float, Integer};
-use serde::Deserialize;
-use sha3::{Digest, Sha3_256};
-use std::{
-    cmp::{Ord, Ordering},
-    collections::{BinaryHeap, HashMap},
-    fs::File,
+use racoon_core::{
+    chain::{Block, Validator},
+    event::{EventQueue, TimedEvent},
+    stake::{powers, StakeDistribution},
+    weight::weight_exp,
+    FLOAT_PRECISION,
 };
-
-/// Precisions in bits of the floating point numbers.
-const FLOAT_PRECISION: u32 = 53;
+use rug::Float;
+use serde::Deserialize;
+use std::{collections::HashMap, fs::File};
 
 #[derive(Clone, Debug, Deserialize)]
 struct Config {
     validators_count: usize,
-    heights_per_epoch: u64,
-    stake_spread_factor: u64,
+    stake_distribution: StakeDistribution,
     block_time: u64,
     max_weight_time: u64,
     finalization_weight: u64,
@@ -23,7 +21,6 @@ struct Config {
     stop_height: u64,
 }
 
-#[allow(clippy::cognitive_complexity)]
 fn main() {
     simple_logger::init_with_level(log::Level::Info).unwrap();
     // simple_logger::init().unwrap();
@@ -31,272 +28,336 @@ fn main() {
 
     log::debug!("Config : {:#?}", config);
 
-    let mut event_queue = BinaryHeap::new();
-    let mut validators: Vec<_> = powers(config.validators_count, config.stake_spread_factor)
+    let mut simulation = Simulation::new(config);
+
+    log::info!("Starting event loop ...");
+
+    simulation.run();
+
+    println!();
+    log::info!("No more events, stopping !");
+
+    // log::debug!("Blocks : {:#?}", simulation.blocks);
+    log::info!("Validators : {:#?}", simulation.validators);
+}
+
+/// State of the simulation.
+struct Simulation {
+    config: Config,
+    event_queue: EventQueue<Event>,
+    validators: Vec<Validator>,
+    /// ID of the next created block.
+    next_block_id: u64,
+    blocks: HashMap<u64, Block>,
+    /// Finalized block ID at each height.
+    finalized_blocks: HashMap<u64, u64>,
+}
+
+impl Simulation {
+    fn new(config: Config) -> Self {
+        let validators: Vec<_> = powers(
+            &config.stake_distribution,
+            config.validators_count,
+            FLOAT_PRECISION,
+        )
         .into_iter()
         .inspect(|power| log::debug!("Registered validator with power {}", power))
         .map(Validator::from_power)
         .collect();
 
-    let mut next_block_id = 1; // 0 is genesis
-    let mut blocks = HashMap::<u64, Block>::new();
-    let mut finalized_blocks = HashMap::<u64, u64>::new();
+        let mut event_queue = EventQueue::new();
 
-    log::info!("Starting event loop ...");
+        for i in 0..validators.len() {
+            event_queue.push(TimedEvent {
+                time: 0,
+                validator_id: i,
+                event: Event::BlockReceived { block: 0 },
+            })
+        }
 
-    for i in 0..validators.len() {
-        event_queue.push(TimedEvent {
-            time: 0,
-            target: i,
-            event: Event::BlockReceived { block: 0 },
-        })
+        Self {
+            config,
+            event_queue,
+            validators,
+            next_block_id: 1, // 0 is genesis
+            blocks: HashMap::new(),
+            finalized_blocks: HashMap::new(),
+        }
     }
 
-    'event_loop: while let Some(event) = event_queue.pop() {
-        // println!();
-        log::trace!("---------- Event : {:?}", event);
-
-        let TimedEvent {
-            time,
-            target,
-            event,
-        } = event;
-
-        match event {
-            Event::BlockReceived { block } => {
-                if block == 0 {
-                    log::trace!("Genesis block");
-
-                    start_vdf(
-                        &config,
-                        &mut event_queue,
-                        time + config.block_time,
-                        0,
-                        0,
-                        1,
-                        target,
-                        &validators[target].power,
-                    );
-
-                    start_vdf(
-                        &config,
-                        &mut event_queue,
-                        time + config.block_time * 2,
-                        0,
-                        0,
-                        2,
-                        target,
-                        &validators[target].power,
-                    );
-                } else {
-                    log::trace!("Received block #{} : {:?}", block, blocks[&block]);
-
-                    let validator = &mut validators[target];
-
-                    let (mut fork_weight, mut maybe_finalizable_block) =
-                        match branch_weight(&blocks, validator.finalized_block, block) {
-                            Some(res) => res,
-                            None => {
-                                log::warn!(
-                                    "Received fork not containing finalized block, ignoring ..."
-                                );
-                                continue;
-                            }
-                        };
-
-                    log::trace!(
-                        "Current fork weight sum : {}",
-                        validator.current_fork_weight
-                    );
-                    log::trace!("Received fork weight sum : {}", fork_weight);
-
-                    if fork_weight > validator.current_fork_weight {
-                        log::debug!("Switch to received fork (head #{})", block);
-
-                        validator.current_head = block;
-                        validator.current_fork_weight = fork_weight.clone();
-
-                        if blocks[&block].height < config.stop_height {
-                            start_vdf(
-                                &config,
-                                &mut event_queue,
-                                time + config.block_time * 2,
-                                0,
-                                block,
-                                blocks[&block].height + 2,
-                                target,
-                                &validators[target].power,
-                            );
-                        }
-
-                        while fork_weight > config.finalization_weight {
-                            let validator = &mut validators[target];
-                            let finalized_height = blocks[&maybe_finalizable_block].height;
-
-                            log::info!(
-                                "Validator {} finalized block #{} (height: {})",
-                                target,
-                                maybe_finalizable_block,
-                                finalized_height
-                            );
-
-                            if let Some(other_finalized) = finalized_blocks.get(&finalized_height) {
-                                if *other_finalized != maybe_finalizable_block {
-                                    log::error!("FINALIZATION DIVERGENCE");
-                                    break 'event_loop;
-                                }
-                            } else {
-                                finalized_blocks.insert(finalized_height, maybe_finalizable_block);
-                            }
-
-                            validator.finalized_block = maybe_finalizable_block;
-
-                            let res =
-                                branch_weight(&blocks, validator.finalized_block, block).unwrap();
-                            fork_weight = res.0;
-                            maybe_finalizable_block = res.1;
-
-                            validator.current_fork_weight = fork_weight.clone();
-                        }
-                    } else {
-                        log::trace!("Staying on current fork (head #{})", validator.current_head);
+    /// Process events until there is none left or finalization diverged.
+    fn run(&mut self) {
+        while let Some(event) = self.event_queue.pop() {
+            // println!();
+            log::trace!("---------- Event : {:?}", event);
+
+            let TimedEvent {
+                time,
+                validator_id: target,
+                event,
+            } = event;
+
+            match event {
+                Event::BlockReceived { block: 0 } => self.receive_genesis(time, target),
+                Event::BlockReceived { block } => {
+                    if !self.receive_block(time, target, block) {
+                        return;
                     }
                 }
+                Event::VdfFinished {
+                    input_block,
+                    output_height,
+                    weight,
+                } => self.finish_vdf(time, target, input_block, output_height, weight),
             }
-            Event::VdfFinished {
-                input_block,
-                output_height,
-                weight,
-            } => {
-                if input_block == 0 {
-                    let previous_block = if validators[target].current_head == 0 {
-                        0
-                    } else {
-                        1
-                    };
-
-                    log::trace!(
-                        "Current head #{} (height {})",
-                        validators[target].current_head,
-                        previous_block
-                    );
-
-                    // let block_head = &blocks[&validators[target].current_head];
-
-                    let block = Block {
-                        height: output_height,
-                        previous_block,
-                        validator_id: target,
-                        shard_id: 0,
-                        weight,
-                    };
-
-                    log::trace!("Pushed block #{} : {:?}", next_block_id, block);
-                    blocks.insert(next_block_id, block);
-
-                    for i in 0..validators.len() {
-                        let latency = if target == i { 0 } else { config.latency };
-
-                        event_queue.push(TimedEvent {
-                            time: time + latency,
-                            target: i,
-                            event: Event::BlockReceived {
-                                block: next_block_id,
-                            },
-                        })
-                    }
+        }
+    }
 
-                    next_block_id += 1;
-                } else {
-                    let block_head = &blocks[&validators[target].current_head];
-
-                    log::trace!(
-                        "Current head #{} (height {})",
-                        validators[target].current_head,
-                        block_head.height
-                    );
-
-                    if block_head.previous_block == input_block {
-                        // log::error!(
-                        //     "TODO : Create block from VDF (height {})",
-                        //     block_head.height + 1
-                        // );
-
-                        let block = Block {
-                            height: output_height,
-                            previous_block: validators[target].current_head,
-                            validator_id: target,
-                            shard_id: 0,
-                            weight: weight.clone(),
-                        };
-
-                        log::trace!("Pushed block #{} : {:?}", next_block_id, block);
-                        blocks.insert(next_block_id, block);
-
-                        for i in 0..validators.len() {
-                            let latency = if target == i { 0 } else { config.latency };
-
-                            event_queue.push(TimedEvent {
-                                time: time + latency,
-                                target: i,
-                                event: Event::BlockReceived {
-                                    block: next_block_id,
-                                },
-                            });
-                        }
-
-                        next_block_id += 1;
-                    } else if validators[target].current_head == input_block {
-                        log::debug!(
-                            "VDF based on #{} arrived while it's still the head of validator {} (lucky next height), retrying soon ...",
-                            input_block,
-                            target,
-                        );
-
-                        event_queue.push(TimedEvent {
-                            time: time + config.lucky_retry,
-                            target: target,
-                            event: Event::VdfFinished {
-                                input_block,
-                                output_height,
-                                weight,
-                            },
-                        });
-                    } else {
-                        log::trace!(
-                            "VDF based on #{} while head parent is #{}, ignoring ...",
-                            input_block,
-                            block_head.previous_block
-                        );
-                    }
+    fn receive_genesis(&mut self, time: u64, target: usize) {
+        log::trace!("Genesis block");
+
+        for output_height in 1..=2 {
+            start_vdf(
+                &self.config,
+                &mut self.event_queue,
+                VdfStart {
+                    time: time + self.config.block_time * output_height,
+                    shard_id: 0,
+                    input_block: 0,
+                    output_height,
+                    validator: target,
+                    validator_power: &self.validators[target].power,
+                },
+            );
+        }
+    }
+
+    /// Switch to the fork of a received block if it is heavier, then finalize.
+    /// Returns false if finalization diverged.
+    fn receive_block(&mut self, time: u64, target: usize, block: u64) -> bool {
+        let blocks = &self.blocks;
+        log::trace!("Received block #{} : {:?}", block, blocks[&block]);
+
+        let validator = &mut self.validators[target];
+
+        let (mut fork_weight, mut maybe_finalizable_block) =
+            match branch_weight(blocks, validator.finalized_block_id, block) {
+                Some(res) => res,
+                None => {
+                    log::warn!("Received fork not containing finalized block, ignoring ...");
+                    return true;
                 }
+            };
+
+        log::trace!(
+            "Current fork weight sum : {}",
+            validator.current_fork_weight
+        );
+        log::trace!("Received fork weight sum : {}", fork_weight);
+
+        if fork_weight <= validator.current_fork_weight {
+            log::trace!(
+                "Staying on current fork (head #{})",
+                validator.current_head_id
+            );
+            return true;
+        }
+
+        log::debug!("Switch to received fork (head #{})", block);
+
+        validator.current_head_id = block;
+        validator.current_fork_weight = fork_weight.clone();
+
+        if blocks[&block].height < self.config.stop_height {
+            start_vdf(
+                &self.config,
+                &mut self.event_queue,
+                VdfStart {
+                    time: time + self.config.block_time * 2,
+                    shard_id: 0,
+                    input_block: block,
+                    output_height: blocks[&block].height + 2,
+                    validator: target,
+                    validator_power: &validator.power,
+                },
+            );
+        }
+
+        while fork_weight > self.config.finalization_weight {
+            let finalized_height = blocks[&maybe_finalizable_block].height;
+
+            log::info!(
+                "Validator {} finalized block #{} (height: {})",
+                target,
+                maybe_finalizable_block,
+                finalized_height
+            );
+
+            if let Some(other_finalized) = self.finalized_blocks.get(&finalized_height) {
+                if *other_finalized != maybe_finalizable_block {
+                    log::error!("FINALIZATION DIVERGENCE");
+                    return false;
+                }
+            } else {
+                self.finalized_blocks
+                    .insert(finalized_height, maybe_finalizable_block);
             }
+
+            validator.finalized_block_id = maybe_finalizable_block;
+
+            let res = branch_weight(blocks, validator.finalized_block_id, block).unwrap();
+            fork_weight = res.0;
+            maybe_finalizable_block = res.1;
+
+            validator.current_fork_weight = fork_weight.clone();
         }
+
+        true
     }
 
-    println!();
-    log::info!("No more events, stopping !");
+    /// Create a block from a finished VDF if it still extends the head.
+    fn finish_vdf(
+        &mut self,
+        time: u64,
+        target: usize,
+        input_block: u64,
+        output_height: u64,
+        weight: Float,
+    ) {
+        let current_head_id = self.validators[target].current_head_id;
+
+        if input_block == 0 {
+            let previous_block = if current_head_id == 0 { 0 } else { 1 };
+
+            log::trace!(
+                "Current head #{} (height {})",
+                current_head_id,
+                previous_block
+            );
+
+            // let block_head = &blocks[&validators[target].current_head_id];
+
+            self.push_block(
+                time,
+                Block {
+                    height: output_height,
+                    shard_id: 0,
+                    previous_block_id: previous_block,
+                    validator_id: target,
+                    weight,
+                    time,
+                },
+            );
+
+            return;
+        }
+
+        let block_head = &self.blocks[&current_head_id];
+
+        log::trace!(
+            "Current head #{} (height {})",
+            current_head_id,
+            block_head.height
+        );
+
+        if block_head.previous_block_id == input_block {
+            // log::error!(
+            //     "TODO : Create block from VDF (height {})",
+            //     block_head.height + 1
+            // );
+
+            self.push_block(
+                time,
+                Block {
+                    height: output_height,
+                    shard_id: 0,
+                    previous_block_id: current_head_id,
+                    validator_id: target,
+                    weight,
+                    time,
+                },
+            );
+        } else if current_head_id == input_block {
+            log::debug!(
+                "VDF based on #{} arrived while it's still the head of validator {} (lucky next height), retrying soon ...",
+                input_block,
+                target,
+            );
+
+            self.event_queue.push(TimedEvent {
+                time: time + self.config.lucky_retry,
+                validator_id: target,
+                event: Event::VdfFinished {
+                    input_block,
+                    output_height,
+                    weight,
+                },
+            });
+        } else {
+            log::trace!(
+                "VDF based on #{} while head parent is #{}, ignoring ...",
+                input_block,
+                block_head.previous_block_id
+            );
+        }
+    }
 
-    // log::debug!("Blocks : {:#?}", blocks);
-    log::info!("Validators : {:#?}", validators);
+    /// Store a new block and send it to every validator.
+    fn push_block(&mut self, time: u64, block: Block) {
+        let creator = block.validator_id;
+
+        log::trace!("Pushed block #{} : {:?}", self.next_block_id, block);
+        self.blocks.insert(self.next_block_id, block);
+
+        for i in 0..self.validators.len() {
+            let latency = if creator == i { 0 } else { self.config.latency };
+
+            self.event_queue.push(TimedEvent {
+                time: time + latency,
+                validator_id: i,
+                event: Event::BlockReceived {
+                    block: self.next_block_id,
+                },
+            })
+        }
+
+        self.next_block_id += 1;
+    }
 }
 
-fn start_vdf(
-    config: &Config,
-    event_queue: &mut BinaryHeap<TimedEvent>,
+/// VDF started by a validator.
+struct VdfStart<'a> {
+    /// Time at which the VDF starts.
     time: u64,
+    /// Shard of the created block.
     shard_id: u64,
-    vdf_input_block: u64,
-    vdf_output_height: u64,
+    /// ID of the block used as VDF input.
+    input_block: u64,
+    /// Height of the block created with the VDF output.
+    output_height: u64,
+    /// Validator computing the VDF.
     validator: usize,
-    validator_power: &Float,
-) {
-    let weight = block_weight(
-        b"seed",
+    /// Power of the validator.
+    validator_power: &'a Float,
+}
+
+fn start_vdf(config: &Config, event_queue: &mut EventQueue<Event>, vdf: VdfStart) {
+    let VdfStart {
+        time,
         shard_id,
-        vdf_output_height,
+        input_block: vdf_input_block,
+        output_height: vdf_output_height,
         validator,
         validator_power,
+    } = vdf;
+
+    let weight = weight_exp(
+        b"seed",
+        validator_power,
+        vdf_output_height,
+        shard_id,
+        validator as u64,
+        FLOAT_PRECISION,
     );
 
     log::trace!("weight = {}", weight);
@@ -313,7 +374,7 @@ fn start_vdf(
 
     event_queue.push(TimedEvent {
         time: time + vdf_time,
-        target: validator,
+        validator_id: validator,
         event: Event::VdfFinished {
             input_block: vdf_input_block,
             output_height: vdf_output_height,
@@ -344,39 +405,12 @@ fn branch_weight(
         weight_sum += block.weight.clone();
 
         last_block = head;
-        head = block.previous_block;
+        head = block.previous_block_id;
     }
 
     Some((weight_sum, last_block))
 }
 
-#[derive(Debug, Clone)]
-struct TimedEvent {
-    time: u64,
-    target: usize,
-    event: Event,
-}
-
-impl PartialEq for TimedEvent {
-    fn eq(&self, other: &Self) -> bool {
-        self.time == other.time
-    }
-}
-
-impl Eq for TimedEvent {}
-
-impl PartialOrd for TimedEvent {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        other.time.partial_cmp(&self.time)
-    }
-}
-
-impl Ord for TimedEvent {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.time.cmp(&self.time)
-    }
-}
-
 #[derive(Debug, Clone)]
 enum Event {
     BlockReceived {
@@ -388,94 +422,3 @@ enum Event {
         weight: Float,
     },
 }
-
-#[derive(Debug, Clone)]
-struct Block {
-    height: u64,
-    previous_block: u64,
-    validator_id: usize,
-    shard_id: u64,
-    weight: Float,
-}
-
-#[derive(Debug, Clone)]
-struct Validator {
-    power: Float,
-    finalized_block: u64,
-    current_head: u64,
-    current_fork_weight: Float,
-}
-
-impl Validator {
-    fn from_power(power: Float) -> Self {
-        Validator {
-            power,
-            finalized_block: 0,
-            current_head: 0,
-            current_fork_weight: Float::with_val(FLOAT_PRECISION, 0),
-        }
-    }
-}
-
-fn block_random(epoch_seed: &[u8], shard_id: u64, block_height: u64, validator_id: u64) -> Float {
-    let mut hasher = Sha3_256::new();
-    hasher.input(epoch_seed);
-    hasher.input(shard_id.to_be_bytes());
-    hasher.input(block_height.to_be_bytes());
-    hasher.input(validator_id.to_be_bytes());
-
-    let hash = hasher.result();
-    let hash = Integer::from_digits(&hash, Order::Lsf);
-    Float::with_val(FLOAT_PRECISION, hash)
-}
-
-fn block_weight(
-    epoch_seed: &[u8],
-    shard_id: u64,
-    block_height: u64,
-    validator_id: usize,
-    validator_power: &Float,
-) -> Float {
-    let rand = block_random(epoch_seed, shard_id, block_height, validator_id as u64);
-
-    let max = Float::with_val(FLOAT_PRECISION, 2).pow(256);
-    let rand: Float = rand / max;
-
-    rand.pow(Float::with_val(FLOAT_PRECISION, 1 / validator_power))
-}
-
-fn powers(count: usize, spread_factor: u64) -> Vec<Float> {
-    let mut stakes = vec![Float::with_val(FLOAT_PRECISION, 0); count];
-    let mut stakes_sum = Float::with_val(FLOAT_PRECISION, 0);
-
-    // generate stakes and stakes sum
-    for (i, s) in stakes.iter_mut().enumerate() {
-        let stake = stake(i as u64, spread_factor);
-        stakes_sum += stake.clone();
-        *s = stake;
-    }
-
-    stakes.sort_by(|a, b| b.partial_cmp(a).unwrap()); // highest first
-    stakes
-        .into_iter()
-        .map(|s| Float::with_val(FLOAT_PRECISION, s) / stakes_sum.clone())
-        .collect()
-}
-
-fn stake(id: u64, spread_factor: u64) -> Float {
-    let mut hasher = Sha3_256::new();
-    hasher.input(b"validator");
-    hasher.input(id.to_be_bytes());
-    let stake = hasher.result();
-
-    let stake = Integer::from_digits(&stake, Order::Lsf);
-    let stake = Float::with_val(FLOAT_PRECISION, stake);
-
-    let hash_max = Float::with_val(FLOAT_PRECISION, 2).pow(256);
-
-    let stake: Float = stake / hash_max;
-    let stake: Float = stake * 10;
-    let stake = stake.pow(spread_factor);
-
-    1 + stake
-}
@@ -1,123 +1,291 @@
-use rug::{integer::Order, ops::Pow, Float, Integer};
+mod network;
+mod report;
+mod shards;
+
+use network::{Network, NetworkConfig};
+use racoon_core::{
+    behaviour::{BehaviourConfig, Publication},
+    chain::{Block, Validator},
+    event::{EventQueue, TimedEvent},
+    stake::{powers, StakeDistribution},
+    vdf::Vdf,
+    weight::block_weight,
+    FLOAT_PRECISION,
+};
+use rug::Float;
 use serde::Deserialize;
 use sha3::{Digest, Sha3_256};
 use std::{
-    cmp::Ordering,
-    collections::{BTreeMap, BinaryHeap},
+    collections::{BTreeMap, BTreeSet},
     fs::File,
+    time::Instant,
 };
 use tracing::instrument;
 
-/// Precisions in bits of the floating point numbers.
-const FLOAT_PRECISION: u32 = 53;
-
 /// Binary config.
 #[derive(Clone, Debug, Deserialize)]
 struct Config {
     /// Amount of validators.
     validators_count: usize,
-    /// How much stakes are heterogenous between validators.
-    stake_spread_factor: u64,
+    /// Distribution of validators stakes.
+    stake_distribution: StakeDistribution,
+    /// Amount of shards, each one running its own chain.
+    shards: u64,
+    /// Amount of shards a validator produces blocks on during an epoch.
+    shards_per_validator: u64,
+    /// Number of heights of an epoch. Validators are assigned to other shards
+    /// and the weights seed changes at each epoch.
+    heights_per_epoch: u64,
+    /// Source of the weights seed of each epoch.
+    epoch_seed: EpochSeed,
+    /// If set, shard 0 is the beacon chain, followed by all validators, and
+    /// includes the last finalized header of each shard chain every given
+    /// amount of heights.
+    crosslink_period: Option<u64>,
 
     /// Number of VDF ticks between 2 consecutive blocks.
     vdf_block_ticks: u64,
     /// Max number of VDF ticks that can be added relative to the block weight.
     vdf_max_weight_ticks: u64,
-    /// Number of ticks a message from a validator takes to reach another one.
-    latency_ticks: u64,
+    /// Number of ticks a validator takes to verify the VDF of a received block.
+    vdf_verify_ticks: u64,
     /// Number of ticks a validator waits to try again a valid early VDF.
     vdf_apply_retry_ticks: u64,
-
+    /// Replace the ticks above with durations measured on a real VDF.
+    vdf_calibration: Option<VdfCalibration>,
+
+    /// Delays of messages between validators.
+    network: NetworkConfig,
+    /// Strategy of validators, by id. Unlisted validators are honest.
+    behaviours: BTreeMap<usize, BehaviourConfig>,
+    /// Fraction of its stake a validator loses when caught signing 2 blocks at
+    /// the same height. Applied once, as a stake change.
+    slashing_penalty: Option<f64>,
+    /// Stake changes submitted by validators.
+    stake_changes: Vec<StakeChangeConfig>,
+    /// Number of heights after the last finalized height a stake change is
+    /// included at. It is applied at the next epoch boundary.
+    stake_change_delay: u64,
+    /// Validators moving all their stake to the one of them with the best
+    /// weights in the next epoch each time they learn its seed.
+    stake_grinding: Option<Vec<usize>>,
+
+    /// Rule used by validators to choose the fork they follow.
+    fork_choice: ForkChoice,
+    /// Private fork published at once by a coalition.
+    long_range_attack: Option<LongRangeAttack>,
     /// Cumulative weight necessary to finalize a block.
     finalization_weight: u64,
     /// Height at which a validator stops producing blocks (to stop the simulation).
     stop_height: u64,
     /// Step at which simulation is stopped. None don't stop. Usefull when debugging.
     step_stop: Option<u64>,
+    /// CSV file receiving the finality and cross-link latencies of each
+    /// finalized block, as read by the topology simulation of racoon_weight.
+    latencies_output: Option<String>,
 }
 
-/// A simulation event.
-#[derive(Debug, Clone)]
-enum Event {
-    /// A block has been received by the validator.
-    BlockReceived { block_id: u64 },
-    /// The validator has finished a VDF.
-    VdfFinished {
-        input_block_id: u64,
-        output_block_height: u64,
-        weight: Float,
-    },
+/// Real VDF used to calibrate the simulation.
+/// Ticks become nanoseconds of this machine, network ones included.
+#[derive(Clone, Debug, Deserialize)]
+struct VdfCalibration {
+    /// Size in bits of the RSA modulus.
+    modulus_bits: u32,
+    /// Number of VDF iterations between 2 consecutive blocks.
+    block_iterations: u64,
+    /// Max number of VDF iterations that can be added relative to the block weight.
+    max_weight_iterations: u64,
+    /// Nanoseconds a validator waits to try again a valid early VDF.
+    apply_retry_nanos: u64,
 }
 
-/// A timed simulation event.
-#[derive(Debug, Clone)]
-struct TimedEvent {
-    /// Time at which an event occurs.
+/// Source of the seed used to compute blocks weights during an epoch.
+#[derive(Clone, Debug, Deserialize)]
+enum EpochSeed {
+    /// Same seed for every epoch.
+    Fixed,
+    /// Hash of the last block of epoch `e - 2` finalized on shard 0 (the
+    /// beacon chain if any), so every validator knows it a whole epoch before
+    /// using it. Epochs 0 and 1 use the fixed seed.
+    FinalizedBlock,
+}
+
+/// Stake joining (positive amount) or leaving (negative amount) the stake of a
+/// validator. Amounts are fractions of the initial total stake.
+#[derive(Clone, Debug, Deserialize)]
+struct StakeChangeConfig {
+    validator: usize,
+    /// Tick at which the change is submitted.
     time: u64,
-    /// Validator reacting to this event.
-    validator_id: usize,
-    /// Simulation events.
-    event: Event,
+    amount: f64,
 }
 
-impl PartialEq for TimedEvent {
-    fn eq(&self, other: &Self) -> bool {
-        self.time == other.time
-    }
+/// Fork choice rule.
+/// Blocks are finalized according to `finalization_weight` whatever the rule.
+#[derive(Clone, Debug, Deserialize)]
+enum ForkChoice {
+    /// Heaviest sum of the blocks weights since the finalized block.
+    /// A block too light to change the sum is refused, even on top of the
+    /// head, which can stall a shard once every validator signed the next
+    /// height.
+    FullSum,
+    /// Heaviest sum of the blocks weights over the last `blocks` heights of
+    /// the highest fork, preventing old forks from catching up after a
+    /// massive publication.
+    SlidingWindow { blocks: u64 },
+    /// GHOST-like : starting from the finalized block, follow the child with
+    /// the heaviest subtree of received blocks.
+    Ghost,
+}
+
+/// A coalition privately builds a fork from an old finalized block of shard 0
+/// with its own VDF results, then publishes it at once.
+#[derive(Clone, Debug, Deserialize)]
+struct LongRangeAttack {
+    /// Validators of the coalition. They keep following the protocol on the
+    /// public chain.
+    coalition: Vec<usize>,
+    /// Height of the finalized block the fork starts from.
+    fork_height: u64,
+    /// Tick at which the fork is published.
+    publish_time: u64,
 }
 
-impl Eq for TimedEvent {}
+impl LongRangeAttack {
+    /// Check that the coalition is a non-empty set of existing validators.
+    fn validate(&self, validators_count: usize) {
+        assert!(
+            !self.coalition.is_empty(),
+            "long-range attack coalition is empty"
+        );
 
-impl PartialOrd for TimedEvent {
-    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
-        other.time.partial_cmp(&self.time)
+        for &validator_id in &self.coalition {
+            assert!(
+                validator_id < validators_count,
+                "long-range attack coalition member {} is not a validator",
+                validator_id
+            );
+        }
     }
 }
 
-impl Ord for TimedEvent {
-    fn cmp(&self, other: &Self) -> Ordering {
-        other.time.cmp(&self.time)
+impl Config {
+    /// Check that every validator id of the config is an existing validator.
+    fn validate(&self) {
+        let check = |validator_id: usize, what: &str| {
+            assert!(
+                validator_id < self.validators_count,
+                "{} {} is not a validator ({} validators)",
+                what,
+                validator_id,
+                self.validators_count
+            )
+        };
+
+        for (&validator_id, behaviour) in &self.behaviours {
+            check(validator_id, "behaviour of validator");
+
+            if let BehaviourConfig::Refusing { validator } = behaviour {
+                check(*validator, "refused validator");
+            }
+        }
+
+        for change in &self.stake_changes {
+            check(change.validator, "stake change of validator");
+        }
+
+        for &validator_id in self.stake_grinding.iter().flatten() {
+            check(validator_id, "stake grinding member");
+        }
+
+        if let Some(attack) = &self.long_range_attack {
+            attack.validate(self.validators_count);
+        }
+    }
+
+    /// Evaluate and verify a real VDF, then convert VDF ticks to
+    /// nanoseconds according to the measured durations.
+    fn calibrate_vdf(&mut self) {
+        let calibration = match &self.vdf_calibration {
+            Some(calibration) => calibration.clone(),
+            None => return,
+        };
+
+        let vdf = Vdf::from_seed(b"seed", calibration.modulus_bits);
+
+        let start = Instant::now();
+        let (output, proof) = vdf.eval(b"calibration", calibration.block_iterations);
+        let eval_nanos = start.elapsed().as_nanos() as u64;
+
+        let start = Instant::now();
+        assert!(vdf.verify(b"calibration", &output, &proof));
+        let verify_nanos = start.elapsed().as_nanos() as u64;
+
+        let nanos_per_iteration = eval_nanos as f64 / calibration.block_iterations as f64;
+
+        self.vdf_block_ticks = eval_nanos;
+        self.vdf_max_weight_ticks =
+            (calibration.max_weight_iterations as f64 * nanos_per_iteration) as u64;
+        self.vdf_verify_ticks = verify_nanos;
+        self.vdf_apply_retry_ticks = calibration.apply_retry_nanos;
+
+        println!(
+            "Calibrated VDF ({} bits modulus) :",
+            calibration.modulus_bits
+        );
+        println!("Eval nanos : {}", eval_nanos);
+        println!("Verify nanos : {}", verify_nanos);
+        println!("Nanos per iteration : {:.1}", nanos_per_iteration);
+        println!("VDF block ticks : {}", self.vdf_block_ticks);
+        println!("VDF max weight ticks : {}", self.vdf_max_weight_ticks);
+        println!("VDF verify ticks : {}", self.vdf_verify_ticks);
+        println!();
     }
 }
 
-/// A validator.
+/// A simulation event.
 #[derive(Debug, Clone)]
-struct Validator {
-    /// Validator power (% of stake).
-    power: Float,
-    /// Current finalized block (won't reorg to a fork not containing this block).
-    finalized_block_id: u64,
-    /// Current fork head.
-    current_head_id: u64,
-    /// Current fork cumulative weight.
-    current_fork_weight: Float,
-    /// ID of blocks on which VDF have been finished with their N+2 block weight.
-    finished_vdf: BTreeMap<u64, Float>,
-
-    latest_created_height: u64,
+enum Event {
+    /// A block has been received by the validator.
+    BlockReceived { shard_id: u64, block_id: u64 },
+    /// The validator has finished a VDF.
+    VdfFinished {
+        shard_id: u64,
+        input_block_id: u64,
+        output_block_height: u64,
+        weight: Float,
+    },
+    /// The long-range attack coalition publishes its fork.
+    AttackPublished,
+    /// The validator submits a stake change.
+    StakeChange { amount: f64 },
 }
 
-impl Validator {
-    fn from_power(power: Float) -> Self {
-        Validator {
-            power,
-            finalized_block_id: 0,
-            current_head_id: 0,
-            current_fork_weight: Float::with_val(FLOAT_PRECISION, 0),
-            finished_vdf: BTreeMap::new(),
-            latest_created_height: 0,
-        }
-    }
+/// Stake change waiting for its epoch.
+#[derive(Debug, Clone)]
+struct StakeChange {
+    validator_id: usize,
+    amount: f64,
+    /// Last finalized height when the change was submitted.
+    height: u64,
+    /// First epoch the change is applied to.
+    epoch: u64,
+    /// Check if the change is a slashing penalty.
+    slashing: bool,
 }
 
+/// Proof that a validator signed 2 different blocks at the same height.
 #[derive(Debug, Clone)]
-struct Block {
-    height: u64,
-    previous_block_id: u64,
-    validator_id: usize,
-    // shard_id: u64,
-    weight: Float,
+struct SlashingEvidence {
+    /// Time at which the second block was created.
     time: u64,
+    validator_id: usize,
+    shard_id: u64,
+    height: u64,
+    /// IDs of the conflicting blocks.
+    block_ids: (u64, u64),
+    /// IDs of the parents of the conflicting blocks.
+    previous_block_ids: (u64, u64),
 }
 
 /// Simulation state.
@@ -126,15 +294,84 @@ struct Simulation {
     /// Simulation config.
     config: Config,
     /// Pool of events. They will be executed in order of their `time`.
-    event_pool: BinaryHeap<TimedEvent>,
-    /// List of validators.
-    validators: Vec<Validator>,
+    event_pool: EventQueue<Event>,
+    /// Validators state on each shard.
+    validators: Vec<Vec<Validator>>,
     /// Next free ID for block creation.
     next_free_block_id: u64,
     /// Map of id -> block data.
     blocks: BTreeMap<u64, Block>,
-    /// Map of finalized blocks.
-    finalized_blocks: BTreeMap<u64, u64>,
+    /// IDs of the children of each block, by shard (genesis being shared).
+    children: BTreeMap<(u64, u64), Vec<u64>>,
+    /// Map of finalized blocks of each shard.
+    finalized_blocks: Vec<BTreeMap<u64, u64>>,
+    /// Time at which the block of each height has been finalized first, for
+    /// each shard.
+    finalization_times: Vec<BTreeMap<u64, u64>>,
+    /// Shards of each validator, by epoch.
+    shard_assignments: BTreeMap<u64, Vec<BTreeSet<u64>>>,
+    /// Seed of each epoch derived by each validator from its finalized chain.
+    validator_seeds: BTreeMap<(usize, u64), Vec<u8>>,
+    /// Amount of received blocks whose epoch seed has been compared to the
+    /// receiver one.
+    seed_checks: u64,
+    /// Amount of received blocks created with another epoch seed than the
+    /// receiver one.
+    seed_mismatches: u64,
+    /// Amount of received blocks whose epoch seed the receiver couldn't derive
+    /// yet.
+    unverified_seeds: u64,
+    /// Amount of VDF not started because the validator couldn't derive the
+    /// epoch seed yet.
+    unavailable_seeds: u64,
+    /// Last finalized block of each shard included in the fork of each beacon
+    /// block (0 if none).
+    crosslinks: BTreeMap<u64, Vec<u64>>,
+    /// Network between validators.
+    network: Network,
+    /// Amount of block messages lost.
+    dropped_messages: u64,
+    /// Amount of block messages delivered twice.
+    duplicated_messages: u64,
+    /// Time and height of the first finalization divergence.
+    divergence: Option<(u64, u64)>,
+    /// Time, shard and head height at which a shard could no longer grow.
+    stall: Option<(u64, u64, u64)>,
+    /// Block signed by each validator at each shard and height.
+    signed_blocks: BTreeMap<(usize, u64, u64), u64>,
+    /// Hash of the header of each block.
+    block_hashes: BTreeMap<u64, [u8; 32]>,
+    /// Evidences of validators signing 2 blocks at the same height.
+    slashing_evidences: Vec<SlashingEvidence>,
+    /// Validators whose power has been slashed.
+    slashed: BTreeSet<usize>,
+    /// Stake of each validator at startup.
+    initial_stakes: Vec<Float>,
+    /// Submitted stake changes.
+    stake_changes: Vec<StakeChange>,
+    /// Normalized power of each validator, by epoch.
+    epoch_powers: BTreeMap<u64, Vec<Float>>,
+    /// Highest height a VDF has been started for.
+    max_vdf_height: u64,
+    /// Epochs for which the grinding coalition moved its stake.
+    ground_epochs: BTreeSet<u64>,
+    /// ID of blocks which have been the head of at least one validator.
+    accepted_heads: BTreeSet<u64>,
+    /// Amount of blocks removed from the fork of a validator each time it
+    /// switched to a fork not containing its previous head, for each shard.
+    reorg_depths: Vec<Vec<u64>>,
+    /// ID of blocks of the long-range attack fork.
+    attack_blocks: BTreeSet<u64>,
+    /// Height of the highest honest head when the attack fork is published.
+    attack_honest_height: u64,
+    /// Amount of times an attack block has been refused because its fork
+    /// doesn't contain the validator finalized block.
+    attack_refusals: u64,
+    /// Validators outside of the coalition which followed the attack fork.
+    attack_followers: BTreeSet<usize>,
+    /// Time at which all validators agreed on the same head after each
+    /// partition healed.
+    reconvergence: Vec<Option<u64>>,
 
     stop: bool,
 
@@ -143,18 +380,58 @@ struct Simulation {
 
 impl Simulation {
     fn new(config: Config) -> Self {
-        let validators: Vec<_> = powers(config.validators_count, config.stake_spread_factor)
-            .into_iter()
-            .map(Validator::from_power)
-            .collect();
+        config.validate();
+
+        let mut validators: Vec<_> = powers(
+            &config.stake_distribution,
+            config.validators_count,
+            FLOAT_PRECISION,
+        )
+        .into_iter()
+        .map(Validator::from_power)
+        .collect();
+
+        for (&validator_id, behaviour) in &config.behaviours {
+            validators[validator_id].behaviour = behaviour.build();
+        }
 
-        let mut event_pool = BinaryHeap::new();
+        let shards = config.shards as usize;
+        let initial_stakes = validators.iter().map(|v| v.power.clone()).collect();
+        let validators = vec![validators; shards];
+
+        let network = Network::new(config.network.clone(), config.validators_count);
+        let reconvergence = vec![None; network.partitions().len()];
+
+        let mut event_pool = EventQueue::new();
+
+        for shard_id in 0..config.shards {
+            for i in 0..config.validators_count {
+                event_pool.push(TimedEvent {
+                    time: 0,
+                    validator_id: i,
+                    event: Event::BlockReceived {
+                        shard_id,
+                        block_id: 0,
+                    },
+                })
+            }
+        }
 
-        for i in 0..validators.len() {
+        for change in &config.stake_changes {
             event_pool.push(TimedEvent {
-                time: 0,
-                validator_id: i,
-                event: Event::BlockReceived { block_id: 0 },
+                time: change.time,
+                validator_id: change.validator,
+                event: Event::StakeChange {
+                    amount: change.amount,
+                },
+            })
+        }
+
+        if let Some(attack) = &config.long_range_attack {
+            event_pool.push(TimedEvent {
+                time: attack.publish_time,
+                validator_id: attack.coalition[0],
+                event: Event::AttackPublished,
             })
         }
 
@@ -175,7 +452,37 @@ impl Simulation {
             validators,
             next_free_block_id: 1, // 0 is genesis and special case.
             blocks: BTreeMap::new(),
-            finalized_blocks: BTreeMap::new(),
+            children: BTreeMap::new(),
+            finalized_blocks: vec![BTreeMap::new(); shards],
+            finalization_times: vec![BTreeMap::new(); shards],
+            shard_assignments: BTreeMap::new(),
+            validator_seeds: BTreeMap::new(),
+            seed_checks: 0,
+            seed_mismatches: 0,
+            unverified_seeds: 0,
+            unavailable_seeds: 0,
+            crosslinks: BTreeMap::new(),
+            network,
+            dropped_messages: 0,
+            duplicated_messages: 0,
+            divergence: None,
+            stall: None,
+            accepted_heads: BTreeSet::new(),
+            reorg_depths: vec![vec![]; shards],
+            attack_blocks: BTreeSet::new(),
+            attack_honest_height: 0,
+            attack_refusals: 0,
+            attack_followers: BTreeSet::new(),
+            signed_blocks: BTreeMap::new(),
+            block_hashes: BTreeMap::new(),
+            slashing_evidences: vec![],
+            slashed: BTreeSet::new(),
+            initial_stakes,
+            stake_changes: vec![],
+            epoch_powers: BTreeMap::new(),
+            max_vdf_height: 0,
+            ground_epochs: BTreeSet::new(),
+            reconvergence,
             stop: false,
         }
     }
@@ -202,7 +509,9 @@ impl Simulation {
 
     fn next(&mut self) -> bool {
         if let Some(event) = self.event_pool.pop() {
+            let time = event.time;
             self.process_event(event);
+            self.check_reconvergence(time);
             true
         } else {
             false
@@ -210,7 +519,7 @@ impl Simulation {
     }
 
     // #[instrument(skip(self))]
-    fn process_event(&mut self, event: TimedEvent) {
+    fn process_event(&mut self, event: TimedEvent<Event>) {
         let TimedEvent {
             time,
             validator_id,
@@ -218,40 +527,103 @@ impl Simulation {
         } = event;
 
         match event {
-            Event::BlockReceived { block_id } => {
-                self.process_event_block_received(time, validator_id, block_id)
+            Event::BlockReceived { shard_id, block_id } => {
+                self.process_event_block_received(time, shard_id, validator_id, block_id)
             }
             Event::VdfFinished {
+                shard_id,
                 input_block_id,
                 output_block_height,
                 weight,
             } => self.process_event_vdf_finished(
                 time,
+                shard_id,
                 validator_id,
                 input_block_id,
                 output_block_height,
                 weight,
             ),
+            Event::AttackPublished => self.process_event_attack_published(time),
+            Event::StakeChange { amount } => {
+                self.submit_stake_change(validator_id, amount, false);
+            }
+        }
+    }
+
+    /// Record when validators agree on the same head after a partition healed.
+    fn check_reconvergence(&mut self, time: u64) {
+        let pending: Vec<_> = self
+            .network
+            .partitions()
+            .iter()
+            .enumerate()
+            .filter(|(i, partition)| partition.end <= time && self.reconvergence[*i].is_none())
+            .map(|(i, _)| i)
+            .collect();
+
+        if pending.is_empty() {
+            return;
+        }
+
+        if self.validators.iter().all(|shard| {
+            shard
+                .iter()
+                .all(|v| v.current_head_id == shard[0].current_head_id)
+        }) {
+            for i in pending {
+                tracing::debug!(partition = i, time, "Reconverged");
+                self.reconvergence[i] = Some(time);
+            }
         }
     }
 
     #[instrument(skip(self))]
-    fn process_event_block_received(&mut self, time: u64, validator_id: usize, block_id: u64) {
+    fn process_event_block_received(
+        &mut self,
+        time: u64,
+        shard_id: u64,
+        validator_id: usize,
+        block_id: u64,
+    ) {
+        let shard = shard_id as usize;
+
+        if !self.validators[shard][validator_id]
+            .received_blocks
+            .insert(block_id)
+        {
+            tracing::trace!("Block already received, ignoring");
+            return;
+        }
+
+        if shard_id == 0 {
+            self.grind_stake(validator_id);
+        }
+
         if block_id == 0 {
-            self.start_vdf(time, 0, 1, validator_id);
-            self.start_vdf(time, 0, 2, validator_id);
+            self.start_vdf(time, shard_id, 0, 1, validator_id);
+            self.start_vdf(time, shard_id, 0, 2, validator_id);
         } else {
+            self.check_epoch_seed(validator_id, block_id);
+
             // Check block legitimacy.
             let validator_final_block_height =
-                if self.validators[validator_id].finalized_block_id == 0 {
+                if self.validators[shard][validator_id].finalized_block_id == 0 {
                     0
                 } else {
-                    self.blocks[&self.validators[validator_id].finalized_block_id].height
+                    self.blocks[&self.validators[shard][validator_id].finalized_block_id].height
                 };
 
+            if !self.validators[shard][validator_id]
+                .behaviour
+                .accepts(&self.blocks[&block_id])
+            {
+                tracing::trace!("Validator refuses to build on this block, ignoring");
+                return;
+            }
+
             let (mut weight, mut maybe_finalizable_id) = match self.compute_fork_weight(
                 block_id,
-                self.validators[validator_id].finalized_block_id,
+                self.validators[shard][validator_id].finalized_block_id,
                 validator_final_block_height,
             ) {
                 Some(res) => res,
@@ -259,6 +631,9 @@ impl Simulation {
                     tracing::warn!(
                         "Proposed block is not based on the validator finalized block, ignoring"
                     );
+                    if self.attack_blocks.contains(&block_id) {
+                        self.attack_refusals += 1;
+                    }
                     return;
                 }
             };
@@ -267,6 +642,7 @@ impl Simulation {
             if self.blocks[&block_id].height < self.config.stop_height {
                 self.start_vdf(
                     time,
+                    shard_id,
                     block_id,
                     self.blocks[&block_id].height + 2,
                     validator_id,
@@ -275,41 +651,74 @@ impl Simulation {
                 tracing::debug!("Reached stop height");
             }
 
-            if weight <= self.validators[validator_id].current_fork_weight {
-                tracing::trace!(
-                    previous_head = self.validators[validator_id].current_head_id,
-                    previous_sum = %self.validators[validator_id].current_fork_weight,
-                    proposed_head = block_id,
-                    proposed_sum = %weight,
-                    "Refused proposed head"
-                );
-                return;
+            let head_id = match self.choose_head(shard_id, validator_id, block_id, &weight) {
+                Some(head_id) => head_id,
+                None => {
+                    tracing::trace!(
+                        previous_head = self.validators[shard][validator_id].current_head_id,
+                        previous_sum = %self.validators[shard][validator_id].current_fork_weight,
+                        proposed_head = block_id,
+                        proposed_sum = %weight,
+                        "Refused proposed head"
+                    );
+                    return;
+                }
+            };
+
+            // GHOST can choose another head than the received block.
+            if head_id != block_id {
+                let res = self
+                    .compute_fork_weight(
+                        head_id,
+                        self.validators[shard][validator_id].finalized_block_id,
+                        validator_final_block_height,
+                    )
+                    .unwrap();
+
+                weight = res.0;
+                maybe_finalizable_id = res.1;
             }
 
             tracing::trace!(
-                previous_head = self.validators[validator_id].current_head_id,
-                previous_sum = %self.validators[validator_id].current_fork_weight,
-                new_head = block_id,
+                previous_head = self.validators[shard][validator_id].current_head_id,
+                previous_sum = %self.validators[shard][validator_id].current_fork_weight,
+                new_head = head_id,
                 new_sum = %weight,
                 "Accepting new head"
             );
 
+            if self.attack_blocks.contains(&head_id) {
+                self.record_attack_head(validator_id);
+            }
+
             // Accept block.
-            self.validators[validator_id].current_head_id = block_id;
-            self.validators[validator_id].current_fork_weight = weight.clone();
+            self.record_reorg(
+                shard_id,
+                self.validators[shard][validator_id].current_head_id,
+                head_id,
+            );
+            self.accepted_heads.insert(head_id);
+            self.validators[shard][validator_id].current_head_id = head_id;
+            self.validators[shard][validator_id].current_fork_weight = weight.clone();
+
+            // Publish withheld blocks if they are overtaken.
+            if self.blocks[&head_id].validator_id != validator_id {
+                self.release_withheld_blocks(time, shard_id, validator_id, true);
+            }
 
             // Create next block if next head parent already finished its VDF.
-            if let Some(weight) = self.validators[validator_id]
+            if let Some(weight) = self.validators[shard][validator_id]
                 .finished_vdf
-                .get(&self.blocks[&block_id].previous_block_id)
+                .get(&self.blocks[&head_id].previous_block_id)
             {
                 let weight = weight.clone();
 
                 self.create_block(
                     time,
+                    shard_id,
                     validator_id,
-                    self.blocks[&block_id].height + 1,
-                    block_id,
+                    self.blocks[&head_id].height + 1,
+                    head_id,
                     weight,
                 );
             }
@@ -327,35 +736,34 @@ impl Simulation {
 
                 self.progress.set_position(finalized_height);
 
-                if let Some(other_finalized) = self.finalized_blocks.get(&finalized_height) {
+                if let Some(other_finalized) = self.finalized_blocks[shard].get(&finalized_height) {
                     if *other_finalized != maybe_finalizable_id {
                         tracing::error!("FINALIZATION DIVERGENCE");
+                        self.divergence = Some((time, finalized_height));
                         self.stop = true;
                         return;
                     }
                 } else {
-                    self.finalized_blocks
-                        .insert(finalized_height, maybe_finalizable_id);
+                    self.finalized_blocks[shard].insert(finalized_height, maybe_finalizable_id);
+                    self.finalization_times[shard].insert(finalized_height, time);
                 }
 
-                self.validators[validator_id].finalized_block_id = maybe_finalizable_id;
+                self.validators[shard][validator_id].finalized_block_id = maybe_finalizable_id;
 
-                let res = match self.compute_fork_weight(
-                    block_id,
-                    maybe_finalizable_id,
-                    finalized_height,
-                ) {
-                    Some(res) => res,
-                    None => {
-                        tracing::error!(%weight, maybe_finalizable_id, "shouldn't happend");
-                        return;
-                    }
-                };
+                let res =
+                    match self.compute_fork_weight(head_id, maybe_finalizable_id, finalized_height)
+                    {
+                        Some(res) => res,
+                        None => {
+                            tracing::error!(%weight, maybe_finalizable_id, "shouldn't happend");
+                            return;
+                        }
+                    };
 
                 weight = res.0;
                 maybe_finalizable_id = res.1;
 
-                self.validators[validator_id].current_fork_weight = weight.clone();
+                self.validators[shard][validator_id].current_fork_weight = weight.clone();
             }
         }
     }
@@ -364,21 +772,24 @@ impl Simulation {
     fn process_event_vdf_finished(
         &mut self,
         time: u64,
+        shard_id: u64,
         validator_id: usize,
         input_block_id: u64,
         output_block_height: u64,
         weight: Float,
     ) {
-        self.validators[validator_id]
+        let shard = shard_id as usize;
+
+        self.validators[shard][validator_id]
             .finished_vdf
             .insert(input_block_id, weight.clone());
 
-        let head_id = self.validators[validator_id].current_head_id;
+        let head_id = self.validators[shard][validator_id].current_head_id;
 
         if input_block_id == 0 {
             if output_block_height == 1 {
                 tracing::trace!("Creating block on top of genesis");
-                self.create_block(time, validator_id, output_block_height, 0, weight);
+                self.create_block(time, shard_id, validator_id, output_block_height, 0, weight);
                 return;
             }
 
@@ -396,15 +807,31 @@ impl Simulation {
                 }
 
                 tracing::trace!("Creating block on top of genesis child");
-                self.create_block(time, validator_id, output_block_height, head_id, weight);
+                self.create_block(
+                    time,
+                    shard_id,
+                    validator_id,
+                    output_block_height,
+                    head_id,
+                    weight,
+                );
                 return;
             }
         }
 
         if head_id == input_block_id {
+            if self.shard_stalled(shard_id) {
+                let height = self.block_height(head_id);
+                tracing::error!(shard_id, height, "SHARD STALLED");
+                self.stall = Some((time, shard_id, height));
+                self.stop = true;
+                return;
+            }
+
             tracing::warn!(head_id, "Vinished VDF can't be used yet, trying later");
 
             let event = Event::VdfFinished {
+                shard_id,
                 input_block_id,
                 output_block_height,
                 weight,
@@ -430,7 +857,14 @@ impl Simulation {
             return;
         }
 
-        self.create_block(time, validator_id, output_block_height, head_id, weight);
+        self.create_block(
+            time,
+            shard_id,
+            validator_id,
+            output_block_height,
+            head_id,
+            weight,
+        );
     }
 
     #[instrument(skip(self))]
@@ -469,16 +903,221 @@ impl Simulation {
         Some((weight_sum, maybe_finalizable_id))
     }
 
+    /// Head a validator switches to after receiving a block, according to
+    /// the fork choice rule. `weight` is the weight of the block fork since
+    /// the validator finalized block.
+    fn choose_head(
+        &self,
+        shard_id: u64,
+        validator_id: usize,
+        block_id: u64,
+        weight: &Float,
+    ) -> Option<u64> {
+        let validator = &self.validators[shard_id as usize][validator_id];
+
+        match self.config.fork_choice {
+            ForkChoice::FullSum => {
+                if *weight > validator.current_fork_weight {
+                    Some(block_id)
+                } else {
+                    None
+                }
+            }
+            ForkChoice::SlidingWindow { blocks } => {
+                // A child of the head is never lighter, even when its weight
+                // is too low to change the sum.
+                let extends_head =
+                    self.blocks[&block_id].previous_block_id == validator.current_head_id;
+
+                // Both forks are weighted over the same heights.
+                let top_height = std::cmp::max(
+                    self.block_height(block_id),
+                    self.block_height(validator.current_head_id),
+                );
+                let min_height = top_height.saturating_sub(blocks);
+
+                if extends_head
+                    || self.window_weight(block_id, min_height)
+                        > self.window_weight(validator.current_head_id, min_height)
+                {
+                    Some(block_id)
+                } else {
+                    None
+                }
+            }
+            ForkChoice::Ghost => {
+                let head_id = self.ghost_head(shard_id, validator_id);
+
+                if head_id != validator.current_head_id {
+                    Some(head_id)
+                } else {
+                    None
+                }
+            }
+        }
+    }
+
+    /// Sum of the weights of the blocks of a fork above given height.
+    fn window_weight(&self, mut block_head_id: u64, min_height: u64) -> Float {
+        let mut weight_sum = Float::with_val(FLOAT_PRECISION, 0);
+
+        while block_head_id != 0 {
+            let block_head = &self.blocks[&block_head_id];
+
+            if block_head.height <= min_height {
+                break;
+            }
+
+            weight_sum += &block_head.weight;
+            block_head_id = block_head.previous_block_id;
+        }
+
+        weight_sum
+    }
+
+    /// Follow the heaviest subtree from the finalized block of a validator.
+    /// Only blocks received by the validator are followed, and only the
+    /// accepted ones are weighted.
+    fn ghost_head(&self, shard_id: u64, validator_id: usize) -> u64 {
+        let validator = &self.validators[shard_id as usize][validator_id];
+
+        // Received blocks descending from the finalized block, parents first.
+        let mut subtree = vec![validator.finalized_block_id];
+        let mut i = 0;
+        while i < subtree.len() {
+            if let Some(children) = self.children.get(&(shard_id, subtree[i])) {
+                subtree.extend(
+                    children
+                        .iter()
+                        .filter(|id| validator.received_blocks.contains(id)),
+                );
+            }
+            i += 1;
+        }
+
+        let mut subtree_weights = BTreeMap::new();
+        for &block_id in subtree.iter().rev() {
+            let mut weight = Float::with_val(FLOAT_PRECISION, 0);
+
+            if block_id != 0 {
+                let block = &self.blocks[&block_id];
+                if validator.behaviour.accepts(block) {
+                    weight += &block.weight;
+                }
+            }
+
+            for child_weight in self
+                .children
+                .get(&(shard_id, block_id))
+                .into_iter()
+                .flatten()
+                .filter_map(|child_id| subtree_weights.get(child_id))
+            {
+                weight += child_weight;
+            }
+
+            subtree_weights.insert(block_id, weight);
+        }
+
+        let mut head_id = validator.finalized_block_id;
+
+        loop {
+            let heaviest = self
+                .children
+                .get(&(shard_id, head_id))
+                .into_iter()
+                .flatten()
+                .filter(|id| subtree_weights.get(*id).is_some_and(|w| *w > 0))
+                .max_by(|a, b| {
+                    subtree_weights[*a]
+                        .partial_cmp(&subtree_weights[*b])
+                        .unwrap()
+                        .then(b.cmp(a))
+                });
+
+            match heaviest {
+                Some(&child_id) => head_id = child_id,
+                None => return head_id,
+            }
+        }
+    }
+
+    /// Record the depth of the reorg when a validator switches heads.
+    fn record_reorg(&mut self, shard_id: u64, previous_head_id: u64, new_head_id: u64) {
+        let mut previous_id = previous_head_id;
+        let mut new_id = new_head_id;
+
+        // Find the common ancestor.
+        while previous_id != new_id {
+            let previous_height = self.block_height(previous_id);
+            let new_height = self.block_height(new_id);
+
+            if previous_height >= new_height {
+                previous_id = self.blocks[&previous_id].previous_block_id;
+            }
+            if new_height >= previous_height {
+                new_id = self.blocks[&new_id].previous_block_id;
+            }
+        }
+
+        let depth = self.block_height(previous_head_id) - self.block_height(previous_id);
+
+        if depth > 0 {
+            self.reorg_depths[shard_id as usize].push(depth);
+        }
+    }
+
+    /// Height of a block, genesis included.
+    fn block_height(&self, block_id: u64) -> u64 {
+        if block_id == 0 {
+            0
+        } else {
+            self.blocks[&block_id].height
+        }
+    }
+
+    /// Whether no validator of a shard can create a block anymore : no block
+    /// of the shard is on its way, and every pending VDF result either waits
+    /// for a child of the head of its validator or was already used at its
+    /// height.
+    fn shard_stalled(&self, shard_id: u64) -> bool {
+        let validators = &self.validators[shard_id as usize];
+
+        !self.event_pool.iter().any(|e| match &e.event {
+            Event::BlockReceived { shard_id: s, .. } => *s == shard_id,
+            Event::AttackPublished => true,
+            Event::VdfFinished {
+                shard_id: s,
+                input_block_id,
+                output_block_height,
+                ..
+            } => {
+                let validator = &validators[e.validator_id];
+                let head_id = validator.current_head_id;
+
+                *s == shard_id
+                    && validator.latest_created_height < *output_block_height
+                    && (*input_block_id == 0
+                        || (head_id != 0
+                            && self.blocks[&head_id].previous_block_id == *input_block_id))
+            }
+            Event::StakeChange { .. } => false,
+        })
+    }
+
     #[instrument(skip(self))]
     fn create_block(
         &mut self,
         time: u64,
+        shard_id: u64,
         validator_id: usize,
         height: u64,
         previous_block_id: u64,
         weight: Float,
     ) {
-        let latest_created_height = self.validators[validator_id].latest_created_height;
+        let shard = shard_id as usize;
+
+        let latest_created_height = self.validators[shard][validator_id].latest_created_height;
         if height <= latest_created_height {
             tracing::trace!(
                 latest_created_height,
@@ -487,68 +1126,310 @@ impl Simulation {
             return;
         }
 
-        self.validators[validator_id].latest_created_height = latest_created_height;
+        self.validators[shard][validator_id].latest_created_height = height;
 
         let block = Block {
             height,
+            shard_id,
             previous_block_id,
             validator_id,
             weight,
             time,
         };
 
-        tracing::trace!("Pushed block #{} : {:?}", self.next_free_block_id, block);
-        self.blocks.insert(self.next_free_block_id, block);
+        let crosslinks = self.beacon_crosslinks(validator_id, height, previous_block_id);
 
-        for i in 0..self.validators.len() {
-            let latency = if validator_id == i {
-                0
-            } else {
-                self.config.latency_ticks
-            };
+        let block_id = self.insert_block(time, block.clone(), 0);
+        if let Some(crosslinks) = &crosslinks {
+            self.crosslinks.insert(block_id, crosslinks.clone());
+        }
 
-            self.event_pool.push(TimedEvent {
-                time: time + latency,
-                validator_id: i,
-                event: Event::BlockReceived {
-                    block_id: self.next_free_block_id,
-                },
-            })
+        let behaviour = self.validators[shard][validator_id].behaviour.clone();
+
+        if behaviour.equivocates() {
+            // Same parent, other content : a distinct block at the same height.
+            let conflicting_id = self.insert_block(time, block, 1);
+            if let Some(crosslinks) = crosslinks {
+                self.crosslinks.insert(conflicting_id, crosslinks);
+            }
+
+            // Each half of the validators receives a different block.
+            for i in 0..self.config.validators_count {
+                let sent_id = if i % 2 == 0 { block_id } else { conflicting_id };
+                self.send_block(time, validator_id, i, sent_id);
+            }
+
+            return;
         }
 
-        self.next_free_block_id += 1;
+        match behaviour.publication() {
+            Publication::Now => self.publish_block(time, validator_id, block_id, true),
+            Publication::Delayed(ticks) => {
+                self.send_block(time, validator_id, validator_id, block_id);
+                self.publish_block(time + ticks, validator_id, block_id, false);
+            }
+            Publication::Withheld => {
+                self.send_block(time, validator_id, validator_id, block_id);
+                self.validators[shard][validator_id]
+                    .withheld_blocks
+                    .push(block_id);
+                self.release_withheld_blocks(time, shard_id, validator_id, false);
+            }
+        }
     }
 
-    #[instrument(skip(self, current_time))]
-    fn start_vdf(
-        &mut self,
-        current_time: u64,
-        input_block_id: u64,
-        output_block_height: u64,
+    /// Shard headers committed by a beacon block created by a validator on top
+    /// of given block : the ones of its parent, updated with the validator last
+    /// finalized blocks every `crosslink_period` heights.
+    /// None if `previous_block_id` is not on the beacon chain.
+    fn beacon_crosslinks(
+        &self,
         validator_id: usize,
-    ) {
-        let weight = block_weight(
-            b"seed",
-            0,
-            output_block_height,
-            validator_id,
-            &self.validators[validator_id].power,
-        );
+        height: u64,
+        previous_block_id: u64,
+    ) -> Option<Vec<u64>> {
+        let period = self.config.crosslink_period?;
 
-        let vdf_blocks_length = if input_block_id == 0 {
-            output_block_height
-        } else {
-            2
-        };
+        if previous_block_id != 0 && self.blocks[&previous_block_id].shard_id != 0 {
+            return None;
+        }
 
-        let base_ticks = vdf_blocks_length * self.config.vdf_block_ticks;
-        let weight_ticks: Float = self.config.vdf_max_weight_ticks * (1 - weight.clone());
-        let weight_ticks = weight_ticks.to_u32_saturating().unwrap() as u64;
-        let vdf_ticks = base_ticks + weight_ticks;
+        let mut crosslinks = self.parent_crosslinks(previous_block_id);
+
+        if height.is_multiple_of(period) {
+            for (shard, validators) in self.validators.iter().enumerate().skip(1) {
+                let finalized_block_id = validators[validator_id].finalized_block_id;
+
+                if self.block_height(finalized_block_id) > self.block_height(crosslinks[shard]) {
+                    crosslinks[shard] = finalized_block_id;
+                }
+            }
+        }
+
+        Some(crosslinks)
+    }
+
+    /// Shard headers committed by a beacon block (none for genesis or blocks
+    /// created outside of the protocol).
+    fn parent_crosslinks(&self, block_id: u64) -> Vec<u64> {
+        self.crosslinks
+            .get(&block_id)
+            .cloned()
+            .unwrap_or_else(|| vec![0; self.validators.len()])
+    }
+
+    /// Hash of the header of a block, chaining the hash of its parent.
+    /// `payload` stands for the content of the block.
+    fn block_hash(&self, block: &Block, payload: u64) -> [u8; 32] {
+        let previous_hash = self
+            .block_hashes
+            .get(&block.previous_block_id)
+            .copied()
+            .unwrap_or([0; 32]); // genesis
+
+        let mut hasher = Sha3_256::new();
+        hasher.input(b"block");
+        hasher.input(previous_hash);
+        hasher.input(block.height.to_be_bytes());
+        hasher.input(block.shard_id.to_be_bytes());
+        hasher.input((block.validator_id as u64).to_be_bytes());
+        hasher.input(block.time.to_be_bytes());
+        hasher.input(payload.to_be_bytes());
+        hasher.result().into()
+    }
+
+    /// Store a new block and check that its creator didn't already sign
+    /// another block at the same height. Signing the same block again returns
+    /// the stored one.
+    fn insert_block(&mut self, time: u64, block: Block, payload: u64) -> u64 {
+        let key = (block.validator_id, block.shard_id, block.height);
+        let other_id = self.signed_blocks.get(&key).copied();
+
+        if let Some(other_id) = other_id {
+            if self.block_hashes[&other_id] == self.block_hash(&block, payload) {
+                tracing::trace!(other_id, "Block already stored");
+                return other_id;
+            }
+        }
+
+        let validator_id = block.validator_id;
+        let previous_block_id = block.previous_block_id;
+        let block_id = self.store_block(block, payload);
+
+        if let Some(other_id) = other_id {
+            let evidence = SlashingEvidence {
+                time,
+                validator_id,
+                shard_id: key.1,
+                height: key.2,
+                block_ids: (other_id, block_id),
+                previous_block_ids: (self.blocks[&other_id].previous_block_id, previous_block_id),
+            };
+
+            tracing::debug!(?evidence, "Equivocation detected");
+            self.slash(validator_id);
+            self.slashing_evidences.push(evidence);
+        } else {
+            self.signed_blocks.insert(key, block_id);
+        }
+
+        block_id
+    }
+
+    /// Store a new block, without looking for equivocations.
+    fn store_block(&mut self, block: Block, payload: u64) -> u64 {
+        let block_id = self.next_free_block_id;
+        self.next_free_block_id += 1;
+
+        tracing::trace!("Pushed block #{} : {:?}", block_id, block);
+
+        let hash = self.block_hash(&block, payload);
+        self.block_hashes.insert(block_id, hash);
+        self.children
+            .entry((block.shard_id, block.previous_block_id))
+            .or_default()
+            .push(block_id);
+        self.blocks.insert(block_id, block);
+        block_id
+    }
+
+    /// Remove the slashing penalty from the stake of a validator, once. Like
+    /// other stake changes, it is applied from the next epoch it can reach.
+    fn slash(&mut self, validator_id: usize) {
+        let penalty = match self.config.slashing_penalty {
+            Some(penalty) => penalty,
+            None => return,
+        };
+
+        if !self.slashed.insert(validator_id) {
+            return;
+        }
+
+        let (_, epoch) = self.stake_change_epoch();
+        let stake = self.epoch_stakes(epoch)[validator_id].to_f64();
+
+        tracing::debug!(validator_id, %stake, epoch, "Slashed validator");
+        self.submit_stake_change(validator_id, -stake * penalty, true);
+    }
+
+    /// Publish withheld blocks of a validator if its behaviour decides so.
+    fn release_withheld_blocks(
+        &mut self,
+        time: u64,
+        shard_id: u64,
+        validator_id: usize,
+        overtaken: bool,
+    ) {
+        let shard = shard_id as usize;
+        let validator = &self.validators[shard][validator_id];
+
+        if validator.withheld_blocks.is_empty()
+            || !validator
+                .behaviour
+                .releases(validator.withheld_blocks.len(), overtaken)
+        {
+            return;
+        }
+
+        let withheld = std::mem::take(&mut self.validators[shard][validator_id].withheld_blocks);
+        tracing::trace!(?withheld, overtaken, "Releasing withheld blocks");
+
+        for block_id in withheld {
+            self.publish_block(time, validator_id, block_id, false);
+        }
+    }
+
+    /// Send a block to every validator, including its creator or not.
+    fn publish_block(&mut self, time: u64, validator_id: usize, block_id: u64, to_creator: bool) {
+        for i in 0..self.config.validators_count {
+            if i != validator_id || to_creator {
+                self.send_block(time, validator_id, i, block_id);
+            }
+        }
+    }
+
+    /// Send a block from a validator to another one through the network.
+    fn send_block(&mut self, time: u64, validator_id: usize, to: usize, block_id: u64) {
+        let shard_id = self.blocks[&block_id].shard_id;
+
+        let deliveries = self
+            .network
+            .block_deliveries(validator_id, to, block_id, time);
+
+        match deliveries.len() {
+            0 => self.dropped_messages += 1,
+            2 => self.duplicated_messages += 1,
+            _ => (),
+        }
+
+        for delay in deliveries {
+            let latency = if validator_id == to {
+                0
+            } else {
+                delay.saturating_add(self.config.vdf_verify_ticks)
+            };
+
+            self.event_pool.push(TimedEvent {
+                time: time.saturating_add(latency),
+                validator_id: to,
+                event: Event::BlockReceived { shard_id, block_id },
+            })
+        }
+    }
+
+    #[instrument(skip(self, current_time))]
+    fn start_vdf(
+        &mut self,
+        current_time: u64,
+        shard_id: u64,
+        input_block_id: u64,
+        output_block_height: u64,
+        validator_id: usize,
+    ) {
+        self.max_vdf_height = std::cmp::max(self.max_vdf_height, output_block_height);
+
+        let seed = match self.validator_seed(validator_id, output_block_height) {
+            Some(seed) => seed,
+            None => {
+                tracing::warn!("Epoch seed is not finalized yet, skipping VDF");
+                self.unavailable_seeds += 1;
+                return;
+            }
+        };
+
+        if !self.is_assigned(shard_id, validator_id, output_block_height) {
+            tracing::trace!("Validator not assigned to this shard at this height");
+            return;
+        }
+
+        let power = self.shard_power(shard_id, validator_id, output_block_height);
+
+        if power == 0 {
+            tracing::trace!("Validator has no stake in this epoch");
+            return;
+        }
+
+        let weight = block_weight(
+            &seed,
+            shard_id,
+            output_block_height,
+            validator_id,
+            &power,
+            FLOAT_PRECISION,
+        );
+
+        let vdf_blocks_length = if input_block_id == 0 {
+            output_block_height
+        } else {
+            2
+        };
+
+        let vdf_ticks = self.vdf_ticks(vdf_blocks_length, &weight);
 
         tracing::trace!(%weight, end_time = current_time + vdf_ticks, "Schedule VDF");
 
         let event = Event::VdfFinished {
+            shard_id,
             input_block_id,
             output_block_height,
             weight,
@@ -561,103 +1442,410 @@ impl Simulation {
         });
     }
 
-    #[instrument(skip(self))]
-    pub fn print_stats(&self) {
-        self.progress.finish();
+    /// Height of the block from which the seed of given epoch is derived.
+    /// None if the epoch uses the fixed seed.
+    fn seed_height(&self, epoch: u64) -> Option<u64> {
+        match self.config.epoch_seed {
+            EpochSeed::FinalizedBlock if epoch >= 2 => {
+                Some((epoch - 1) * self.config.heights_per_epoch - 1)
+            }
+            _ => None,
+        }
+    }
+
+    /// Seed derived from the content of a block.
+    fn block_seed(&self, block_id: u64) -> Vec<u8> {
+        let block_hash = self.block_hashes.get(&block_id).copied().unwrap_or([0; 32]); // genesis
 
-        self.print_fairness();
-        self.print_average_time();
+        let mut hasher = Sha3_256::new();
+        hasher.input(b"epoch seed");
+        hasher.input(block_hash);
+        hasher.result().to_vec()
     }
 
-    fn print_fairness(&self) {
-        let mut validators_wins = vec![0; self.config.validators_count];
+    /// Seed of given epoch, derived from the first block finalized at the seed
+    /// height. Falls back to the highest block finalized below it, if any,
+    /// when no validator finalized it yet.
+    fn epoch_seed(&self, epoch: u64) -> Vec<u8> {
+        let height = match self.seed_height(epoch) {
+            Some(height) => height,
+            None => return b"seed".to_vec(),
+        };
 
-        for (_height, block_id) in &self.finalized_blocks {
-            let block = &self.blocks[&block_id];
-            validators_wins[block.validator_id] += 1;
+        match self.finalized_blocks[0].range(..=height).next_back() {
+            Some((&finalized_height, &block_id)) => {
+                if finalized_height != height {
+                    tracing::warn!(epoch, height, finalized_height, "Epoch seed not finalized");
+                }
+                self.block_seed(block_id)
+            }
+            None => {
+                tracing::warn!(epoch, height, "No finalized block for the epoch seed");
+                b"seed".to_vec()
+            }
         }
+    }
 
-        let mut diff_sum = 0.0;
+    /// Seed of the epoch of given height as derived by a validator from its
+    /// finalized chain. None if the validator hasn't finalized the seed height
+    /// yet.
+    fn validator_seed(&mut self, validator_id: usize, height: u64) -> Option<Vec<u8>> {
+        let epoch = height / self.config.heights_per_epoch;
 
-        for i in 0..self.config.validators_count {
-            let winrate = validators_wins[i] as f64 / self.finalized_blocks.len() as f64;
-            let power = self.validators[i].power.to_f64();
-            let diff = winrate - power;
-            diff_sum += diff.abs();
+        let seed_height = match self.seed_height(epoch) {
+            Some(seed_height) => seed_height,
+            None => return Some(b"seed".to_vec()),
+        };
+
+        if let Some(seed) = self.validator_seeds.get(&(validator_id, epoch)) {
+            return Some(seed.clone());
+        }
+
+        let mut block_id = self.validators[0][validator_id].finalized_block_id;
+
+        if self.block_height(block_id) < seed_height {
+            return None;
+        }
+
+        while self.block_height(block_id) > seed_height {
+            block_id = self.blocks[&block_id].previous_block_id;
+        }
+
+        let seed = self.block_seed(block_id);
+        self.validator_seeds
+            .insert((validator_id, epoch), seed.clone());
+
+        Some(seed)
+    }
+
+    /// Compare the epoch seed used by the creator of a received block with the
+    /// receiver one, so all validators must switch to the same seed at the
+    /// same height.
+    fn check_epoch_seed(&mut self, validator_id: usize, block_id: u64) {
+        let block = &self.blocks[&block_id];
+        let (height, creator) = (block.height, block.validator_id);
+        let epoch = height / self.config.heights_per_epoch;
+
+        if self.seed_height(epoch).is_none() {
+            return;
         }
 
-        let fairness = diff_sum / self.config.validators_count as f64;
+        let creator_seed = match self.validator_seeds.get(&(creator, epoch)) {
+            Some(seed) => seed.clone(),
+            None => return,
+        };
+
+        match self.validator_seed(validator_id, height) {
+            Some(seed) => {
+                self.seed_checks += 1;
 
-        println!("Fairness : {:.9}", fairness);
+                if seed != creator_seed {
+                    tracing::error!(validator_id, block_id, epoch, "EPOCH SEED MISMATCH");
+                    self.seed_mismatches += 1;
+                }
+            }
+            None => self.unverified_seeds += 1,
+        }
     }
 
-    fn print_average_time(&self) {
-        let mut diff_sum = 0.0;
-        let mut diff_min = std::u64::MAX;
-        let mut diff_max = 0;
+    /// Power of a validator at given height. Without stake changes, it is the
+    /// validator power, otherwise its power in the epoch.
+    fn power(&mut self, shard_id: u64, validator_id: usize, height: u64) -> Float {
+        if self.stake_changes.is_empty() {
+            return self.validators[shard_id as usize][validator_id]
+                .power
+                .clone();
+        }
 
-        let mut diff_odd_even_sum = 0.0;
-        let mut diff_odd_even_min = std::u64::MAX;
-        let mut diff_odd_even_max = 0;
+        let epoch = height / self.config.heights_per_epoch;
 
-        let mut diff_even_odd_sum = 0.0;
-        let mut diff_even_odd_min = std::u64::MAX;
-        let mut diff_even_odd_max = 0;
+        if !self.epoch_powers.contains_key(&epoch) {
+            let powers = self.compute_epoch_powers(epoch);
+            self.epoch_powers.insert(epoch, powers);
+        }
 
-        let mut odd_even_count = 0;
-        let mut even_odd_count = 0;
+        self.epoch_powers[&epoch][validator_id].clone()
+    }
 
-        let mut iter = self.finalized_blocks.iter().peekable();
+    /// Stake of each validator during an epoch, with the stake changes
+    /// submitted so far.
+    fn epoch_stakes(&self, epoch: u64) -> Vec<Float> {
+        let mut stakes = self.initial_stakes.clone();
 
-        while let Some((height, block_id)) = iter.next() {
-            if let Some((_, next_block_id)) = iter.peek() {
-                let block0 = &self.blocks[block_id];
-                let block1 = &self.blocks[next_block_id];
-                let diff = block1.time - block0.time;
-                diff_sum += diff as f64;
-                diff_min = std::cmp::min(diff_min, diff);
-                diff_max = std::cmp::max(diff_max, diff);
+        for change in self.stake_changes.iter().filter(|c| c.epoch <= epoch) {
+            let stake = &mut stakes[change.validator_id];
+            *stake += change.amount;
 
-                if height % 2 == 0 {
-                    diff_even_odd_sum += diff as f64;
-                    diff_even_odd_min = std::cmp::min(diff_even_odd_min, diff);
-                    diff_even_odd_max = std::cmp::max(diff_even_odd_max, diff);
-                    even_odd_count += 1;
-                } else {
-                    diff_odd_even_sum += diff as f64;
-                    diff_odd_even_min = std::cmp::min(diff_odd_even_min, diff);
-                    diff_odd_even_max = std::cmp::max(diff_odd_even_max, diff);
-                    odd_even_count += 1;
+            if *stake < 0 {
+                *stake = Float::with_val(FLOAT_PRECISION, 0);
+            }
+        }
+
+        stakes
+    }
+
+    /// Power of each validator during an epoch : its stake relative to the
+    /// total stake.
+    fn compute_epoch_powers(&self, epoch: u64) -> Vec<Float> {
+        let stakes = self.epoch_stakes(epoch);
+        let total = Float::with_val(FLOAT_PRECISION, Float::sum(stakes.iter()));
+
+        stakes.into_iter().map(|stake| stake / &total).collect()
+    }
+
+    /// First epoch a stake change submitted now would be applied to, with the
+    /// height it is included at. It is never an epoch a VDF has already been
+    /// started for, so powers of past heights don't change.
+    fn stake_change_epoch(&self) -> (u64, u64) {
+        let heights_per_epoch = self.config.heights_per_epoch;
+        let finalized_height = self.finalized_blocks[0].keys().last().copied().unwrap_or(0);
+        let height = finalized_height + self.config.stake_change_delay;
+
+        let epoch = std::cmp::max(
+            height / heights_per_epoch + 1,
+            self.max_vdf_height / heights_per_epoch + 1,
+        );
+
+        (height, epoch)
+    }
+
+    /// Queue a stake change of a validator for the next possible epoch.
+    fn submit_stake_change(&mut self, validator_id: usize, amount: f64, slashing: bool) {
+        let (height, epoch) = self.stake_change_epoch();
+
+        tracing::debug!(validator_id, %amount, height, epoch, slashing, "Stake change");
+
+        self.stake_changes.push(StakeChange {
+            validator_id,
+            amount,
+            height,
+            epoch,
+            slashing,
+        });
+        self.epoch_powers.retain(|&e, _| e < epoch);
+    }
+
+    /// Move the stake of the grinding coalition to the member with the most
+    /// heights won in the next epoch a stake change can reach, if the member
+    /// receiving a block already knows the seed of this epoch.
+    /// Heights are won by the highest weight of shard 0, the weights of the
+    /// other validators being public.
+    fn grind_stake(&mut self, validator_id: usize) {
+        let coalition = match &self.config.stake_grinding {
+            Some(coalition) if coalition.contains(&validator_id) => coalition.clone(),
+            _ => return,
+        };
+
+        let heights_per_epoch = self.config.heights_per_epoch;
+        let (_, epoch) = self.stake_change_epoch();
+
+        if self.ground_epochs.contains(&epoch) {
+            return;
+        }
+
+        let seed = match self.validator_seed(validator_id, epoch * heights_per_epoch) {
+            Some(seed) => seed,
+            None => return,
+        };
+
+        self.ground_epochs.insert(epoch);
+
+        let stakes = self.epoch_stakes(epoch);
+        let powers = self.compute_epoch_powers(epoch);
+        let coalition_stake = Float::with_val(
+            FLOAT_PRECISION,
+            Float::sum(coalition.iter().map(|&v| &stakes[v])),
+        );
+        let coalition_power = Float::with_val(
+            FLOAT_PRECISION,
+            Float::sum(coalition.iter().map(|&v| &powers[v])),
+        );
+
+        let heights = epoch * heights_per_epoch..(epoch + 1) * heights_per_epoch;
+        let mut wins = vec![0; coalition.len()];
+
+        for height in heights {
+            let best_honest = (0..self.config.validators_count)
+                .filter(|v| !coalition.contains(v) && powers[*v] > 0)
+                .map(|v| block_weight(&seed, 0, height, v, &powers[v], FLOAT_PRECISION))
+                .max_by(|a, b| a.partial_cmp(b).unwrap())
+                .unwrap_or_else(|| Float::with_val(FLOAT_PRECISION, 0));
+
+            for (i, &v) in coalition.iter().enumerate() {
+                if block_weight(&seed, 0, height, v, &coalition_power, FLOAT_PRECISION)
+                    > best_honest
+                {
+                    wins[i] += 1;
                 }
             }
         }
 
-        let average_time = diff_sum / (self.finalized_blocks.len() - 1) as f64;
-        let average_even_odd = diff_even_odd_sum / even_odd_count as f64;
-        let average_odd_even = diff_odd_even_sum / odd_even_count as f64;
+        let best = (0..coalition.len()).max_by_key(|&i| wins[i]).unwrap();
+
+        tracing::debug!(
+            epoch,
+            validator_id = coalition[best],
+            wins = wins[best],
+            "Grinding stake"
+        );
+
+        for (i, &v) in coalition.iter().enumerate() {
+            let target = if i == best {
+                coalition_stake.clone()
+            } else {
+                Float::with_val(FLOAT_PRECISION, 0)
+            };
+            let amount = (target - &stakes[v]).to_f64();
+
+            if amount != 0.0 {
+                self.submit_stake_change(v, amount, false);
+            }
+        }
+    }
+
+    /// Number of ticks of a VDF spanning given amount of blocks and producing
+    /// given weight.
+    fn vdf_ticks(&self, vdf_blocks_length: u64, weight: &Float) -> u64 {
+        let base_ticks = vdf_blocks_length * self.config.vdf_block_ticks;
+        let weight_ticks: Float = self.config.vdf_max_weight_ticks * (1 - weight.clone());
+        base_ticks + weight_ticks.to_integer().unwrap().to_u64().unwrap()
+    }
+
+    /// Build the long-range attack fork as the coalition would have done since
+    /// its base block, then publish it.
+    fn process_event_attack_published(&mut self, time: u64) {
+        let attack = self.config.long_range_attack.clone().unwrap();
+
+        let base_id = match attack.fork_height {
+            0 => 0,
+            height => match self.finalized_blocks[0].get(&height) {
+                Some(&id) => id,
+                None => {
+                    tracing::warn!(height, "Attack base block is not finalized, aborting");
+                    return;
+                }
+            },
+        };
+
+        self.attack_honest_height = self.validators[0]
+            .iter()
+            .map(|v| self.block_height(v.current_head_id))
+            .max()
+            .unwrap();
+
+        // (height, time) of the last 2 blocks of the fork.
+        let mut tail = if base_id == 0 {
+            vec![(0, 0)]
+        } else {
+            let base = &self.blocks[&base_id];
+            let parent_time = if base.previous_block_id == 0 {
+                0
+            } else {
+                self.blocks[&base.previous_block_id].time
+            };
+            vec![(base.height - 1, parent_time), (base.height, base.time)]
+        };
+
+        let mut previous_block_id = base_id;
+        let mut height = attack.fork_height + 1;
+
+        loop {
+            // The coalition uses the epoch seeds of the public chain.
+            let seeds: Option<Vec<_>> = attack
+                .coalition
+                .iter()
+                .map(|&v| self.validator_seed(v, height))
+                .collect();
+
+            let seeds = match seeds {
+                Some(seeds) => seeds,
+                None => break,
+            };
+
+            let powers: Vec<_> = attack
+                .coalition
+                .iter()
+                .map(|&v| self.power(0, v, height))
+                .collect();
+
+            // Best VDF result of the coalition for this height.
+            let (validator_id, weight) = attack
+                .coalition
+                .iter()
+                .zip(seeds.iter().zip(&powers))
+                .map(|(&v, (seed, power))| {
+                    let weight = block_weight(seed, 0, height, v, power, FLOAT_PRECISION);
+                    (v, weight)
+                })
+                .max_by(|a, b| a.1.partial_cmp(&b.1).unwrap())
+                .unwrap();
+
+            // The VDF starts on the block 2 heights below (or genesis).
+            let (input_height, input_time) = tail[tail.len().saturating_sub(2)];
+            let (_, previous_time) = tail[tail.len() - 1];
+            let block_time = std::cmp::max(
+                previous_time,
+                input_time + self.vdf_ticks(height - input_height, &weight),
+            );
+
+            if block_time > time {
+                break;
+            }
+
+            let block = Block {
+                height,
+                shard_id: 0,
+                previous_block_id,
+                validator_id,
+                weight,
+                time: block_time,
+            };
+
+            // The coalition members also sign public blocks at these heights :
+            // the fork is measured by the attack metrics, not slashed.
+            previous_block_id = self.store_block(block, 0);
+            self.attack_blocks.insert(previous_block_id);
+
+            tail.push((height, block_time));
+            height += 1;
+        }
+
+        tracing::debug!(
+            blocks = self.attack_blocks.len(),
+            honest_height = self.attack_honest_height,
+            "Publishing long-range attack"
+        );
 
-        println!("Average block time : {:.1}", average_time);
-        println!("Min block time : {}", diff_min);
-        println!("Max block time : {}", diff_max);
+        let attack_blocks: Vec<_> = self.attack_blocks.iter().copied().collect();
+        for block_id in attack_blocks {
+            let validator_id = self.blocks[&block_id].validator_id;
+            self.publish_block(time, validator_id, block_id, true);
+        }
+    }
 
-        println!("Average even-odd block time : {:.1}", average_even_odd);
-        println!("Min even-odd block time : {}", diff_even_odd_min);
-        println!("Max even-odd block time : {}", diff_even_odd_max);
+    /// Record a validator switching to the attack fork.
+    fn record_attack_head(&mut self, validator_id: usize) {
+        let attack = self.config.long_range_attack.as_ref().unwrap();
 
-        println!("Average odd-even block time : {:.1}", average_odd_even);
-        println!("Min odd-even block time : {}", diff_odd_even_min);
-        println!("Max odd-even block time : {}", diff_odd_even_max);
+        if !attack.coalition.contains(&validator_id) {
+            self.attack_followers.insert(validator_id);
+        }
     }
 }
 
 fn main() {
     init_tracing();
-    let config = config_from_ron_file("config.ron");
+    let mut config = config_from_ron_file("config.ron");
+    config.calibrate_vdf();
 
     let mut simulation = Simulation::new(config);
 
     simulation.run();
     simulation.print_stats();
+
+    if let Some(path) = &simulation.config.latencies_output {
+        simulation.write_latencies(path);
+    }
 }
 
 fn init_tracing() {
@@ -686,74 +1874,405 @@ fn config_from_ron_file(path: &str) -> Config {
     ron::de::from_reader(file).unwrap()
 }
 
-fn block_random(epoch_seed: &[u8], shard_id: u64, block_height: u64, validator_id: u64) -> Float {
-    // let mut hasher = Sha3_256::new();
-    // hasher.input(epoch_seed);
-    // hasher.input(shard_id.to_be_bytes());
-    // hasher.input(block_height.to_be_bytes());
-    // hasher.input(validator_id.to_be_bytes());
-
-    // let hash = hasher.result();
-    // let hash = Integer::from_digits(&hash, Order::Lsf);
-
-    let mut hasher = blake3::Hasher::new();
-    hasher.update(epoch_seed);
-    hasher.update(&shard_id.to_be_bytes());
-    hasher.update(&block_height.to_be_bytes());
-    hasher.update(&validator_id.to_be_bytes());
-    let hash = hasher.finalize();
-    let hash = Integer::from_digits(hash.as_bytes(), Order::Lsf);
-
-    Float::with_val(FLOAT_PRECISION, hash)
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
 
-fn block_weight(
-    epoch_seed: &[u8],
-    shard_id: u64,
-    block_height: u64,
-    validator_id: usize,
-    validator_power: &Float,
-) -> Float {
-    let rand = block_random(epoch_seed, shard_id, block_height, validator_id as u64);
+    /// Default config, shortened.
+    fn config() -> Config {
+        let mut config = config_from_ron_file("config.ron");
+        config.stop_height = 300;
+        config
+    }
 
-    let max = Float::with_val(FLOAT_PRECISION, 2).pow(256);
-    let rand: Float = rand / max;
+    fn run(config: Config) -> Simulation {
+        let mut simulation = Simulation::new(config);
+        simulation.progress = indicatif::ProgressBar::hidden();
+        simulation.run();
+        simulation
+    }
 
-    rand.pow(Float::with_val(FLOAT_PRECISION, 1 / validator_power))
-}
+    #[test]
+    fn honest_validators_never_equivocate() {
+        let mut config = config();
+        config.behaviours.clear();
+        config.slashing_penalty = Some(0.5);
 
-fn powers(count: usize, spread_factor: u64) -> Vec<Float> {
-    let mut stakes = vec![Float::with_val(FLOAT_PRECISION, 0); count];
-    let mut stakes_sum = Float::with_val(FLOAT_PRECISION, 0);
+        let simulation = run(config);
 
-    // generate stakes and stakes sum
-    for (i, s) in stakes.iter_mut().enumerate() {
-        let stake = stake(i as u64, spread_factor);
-        stakes_sum += stake.clone();
-        *s = stake;
+        assert!(simulation.finalized_blocks[0].len() > 200);
+        assert!(simulation.slashing_evidences.is_empty());
+        assert!(simulation.slashed.is_empty());
     }
 
-    stakes.sort_by(|a, b| b.partial_cmp(a).unwrap()); // highest first
-    stakes
-        .into_iter()
-        .map(|s| Float::with_val(FLOAT_PRECISION, s) / stakes_sum.clone())
-        .collect()
-}
+    #[test]
+    fn expected_powers_follow_shard_assignment() {
+        let mut config = config();
+        config.shards = 3;
+        config.crosslink_period = Some(4);
+
+        let simulation = run(config);
+
+        for shard in 1..3 {
+            let powers = simulation.expected_powers(shard);
+            assert!((powers.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+            for (epoch, assignment) in &simulation.shard_assignments {
+                let epoch_powers = simulation.epoch_shard_powers(shard, *epoch);
+                assert!((epoch_powers.iter().sum::<f64>() - 1.0).abs() < 1e-9);
+
+                for (power, shards) in epoch_powers.iter().zip(assignment) {
+                    assert_eq!(*power > 0.0, shards.contains(&(shard as u64)));
+                }
+            }
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "behaviour of validator 20 is not a validator")]
+    fn behaviours_need_existing_validators() {
+        let mut config = config();
+        config.behaviours = vec![(20, BehaviourConfig::Equivocating)]
+            .into_iter()
+            .collect();
+
+        Simulation::new(config);
+    }
+
+    #[test]
+    #[should_panic(expected = "refused validator 42 is not a validator")]
+    fn refused_validator_must_exist() {
+        let mut config = config();
+        config.behaviours = vec![(1, BehaviourConfig::Refusing { validator: 42 })]
+            .into_iter()
+            .collect();
+
+        Simulation::new(config);
+    }
+
+    #[test]
+    #[should_panic(expected = "stake change of validator 20 is not a validator")]
+    fn stake_changes_need_existing_validators() {
+        let mut config = config();
+        config.stake_changes = vec![StakeChangeConfig {
+            validator: 20,
+            time: 0,
+            amount: 0.1,
+        }];
+
+        Simulation::new(config);
+    }
+
+    #[test]
+    #[should_panic(expected = "coalition is empty")]
+    fn long_range_attack_needs_a_coalition() {
+        let mut config = config();
+        config.long_range_attack = Some(LongRangeAttack {
+            coalition: vec![],
+            fork_height: 10,
+            publish_time: 200_000_000,
+        });
+
+        Simulation::new(config);
+    }
 
-fn stake(id: u64, spread_factor: u64) -> Float {
-    let mut hasher = Sha3_256::new();
-    hasher.input(b"validator");
-    hasher.input(id.to_be_bytes());
-    let stake = hasher.result();
+    #[test]
+    fn published_attack_is_not_slashed() {
+        let mut config = config();
+        config.behaviours.clear();
+        config.slashing_penalty = Some(0.5);
+        config.long_range_attack = Some(LongRangeAttack {
+            coalition: vec![0, 1, 2, 3],
+            fork_height: 10,
+            publish_time: 200_000_000,
+        });
+
+        let simulation = run(config);
+
+        // Coalition members signed both public and attack blocks at the same
+        // heights.
+        assert!(simulation.attack_blocks.iter().any(|id| {
+            let block = &simulation.blocks[id];
+            let key = (block.validator_id, block.shard_id, block.height);
+            simulation.signed_blocks.contains_key(&key)
+        }));
+        assert!(simulation.slashing_evidences.is_empty());
+        assert!(simulation.slashed.is_empty());
+    }
+
+    #[test]
+    fn conflicting_blocks_are_slashed_but_not_identical_ones() {
+        let mut config = config();
+        config.slashing_penalty = Some(0.5);
+
+        let mut simulation = Simulation::new(config);
+        let block = Block {
+            height: 1,
+            shard_id: 0,
+            previous_block_id: 0,
+            validator_id: 3,
+            weight: Float::with_val(FLOAT_PRECISION, 0.5),
+            time: 10,
+        };
+
+        let first_id = simulation.insert_block(10, block.clone(), 0);
+        assert_eq!(simulation.insert_block(10, block.clone(), 0), first_id);
+        assert!(simulation.slashing_evidences.is_empty());
+        assert!(simulation.slashed.is_empty());
+
+        let second_id = simulation.insert_block(10, block, 1);
+        assert_ne!(second_id, first_id);
+        assert_ne!(
+            simulation.block_hashes[&first_id],
+            simulation.block_hashes[&second_id]
+        );
+        assert_eq!(simulation.slashing_evidences.len(), 1);
+        assert_eq!(
+            simulation.slashing_evidences[0].block_ids,
+            (first_id, second_id)
+        );
+        assert!(simulation.slashed.contains(&3));
+    }
+
+    /// Simulation holding a hand-built fork of shard 0 :
+    /// genesis <- a (0.5) <- a2 (0.1) and genesis <- b (0.2) <- b2 (0.3),
+    /// b (0.2) <- b2' (0.25). Validator 0 follows a2 and received every block.
+    fn fork(fork_choice: ForkChoice) -> (Simulation, [u64; 5]) {
+        let mut config = config();
+        config.fork_choice = fork_choice;
+
+        let mut simulation = Simulation::new(config);
+        let mut push = |previous_block_id, height, validator_id, weight: f64| {
+            let block = Block {
+                height,
+                shard_id: 0,
+                previous_block_id,
+                validator_id,
+                weight: Float::with_val(FLOAT_PRECISION, weight),
+                time: 0,
+            };
+            simulation.insert_block(0, block, 0)
+        };
+
+        let a = push(0, 1, 1, 0.5);
+        let a2 = push(a, 2, 2, 0.1);
+        let b = push(0, 1, 3, 0.2);
+        let b2 = push(b, 2, 4, 0.3);
+        let b2_bis = push(b, 2, 5, 0.25);
+
+        let validator = &mut simulation.validators[0][0];
+        validator.current_head_id = a2;
+        validator.current_fork_weight = Float::with_val(FLOAT_PRECISION, 0.6);
+        validator.received_blocks = vec![a, a2, b, b2, b2_bis].into_iter().collect();
+
+        (simulation, [a, a2, b, b2, b2_bis])
+    }
+
+    fn weight(weight: f64) -> Float {
+        Float::with_val(FLOAT_PRECISION, weight)
+    }
+
+    #[test]
+    fn full_sum_needs_a_strictly_heavier_fork() {
+        let (mut simulation, [_, a2, _, b2, _]) = fork(ForkChoice::FullSum);
+
+        // b2 fork weighs 0.5 < 0.6
+        assert_eq!(simulation.choose_head(0, 0, b2, &weight(0.5)), None);
+
+        // equal weights keep the current head
+        let b3 = simulation.insert_block(
+            0,
+            Block {
+                height: 3,
+                shard_id: 0,
+                previous_block_id: b2,
+                validator_id: 6,
+                weight: weight(0.1),
+                time: 0,
+            },
+            0,
+        );
+        assert_eq!(simulation.choose_head(0, 0, b3, &weight(0.6)), None);
+        assert_eq!(simulation.choose_head(0, 0, b3, &weight(0.7)), Some(b3));
+
+        // a child of the head too light to change the sum is refused too
+        let a3 = simulation.insert_block(
+            0,
+            Block {
+                height: 3,
+                shard_id: 0,
+                previous_block_id: a2,
+                validator_id: 7,
+                weight: weight(1e-30),
+                time: 0,
+            },
+            0,
+        );
+        let sum: Float = weight(0.6) + weight(1e-30);
+        assert_eq!(simulation.choose_head(0, 0, a3, &sum), None);
+    }
+
+    #[test]
+    fn sliding_window_only_weights_last_heights() {
+        let (simulation, [_, _, _, b2, _]) = fork(ForkChoice::SlidingWindow { blocks: 1 });
+
+        // Over height 2 only, b2 (0.3) beats a2 (0.1) although its fork is
+        // lighter since genesis.
+        assert_eq!(simulation.choose_head(0, 0, b2, &weight(0.5)), Some(b2));
+
+        let (simulation, [_, _, _, b2, _]) = fork(ForkChoice::SlidingWindow { blocks: 2 });
+        assert_eq!(simulation.choose_head(0, 0, b2, &weight(0.5)), None);
+    }
+
+    #[test]
+    fn ghost_follows_heaviest_received_subtree() {
+        let (mut simulation, [_, _, b, b2, b2_bis]) = fork(ForkChoice::Ghost);
 
-    let stake = Integer::from_digits(&stake, Order::Lsf);
-    let stake = Float::with_val(FLOAT_PRECISION, stake);
+        // b subtree weighs 0.75 against 0.6 for a, then b2 is b heaviest child.
+        assert_eq!(
+            simulation.choose_head(0, 0, b2_bis, &weight(0.45)),
+            Some(b2)
+        );
+
+        // Without b2', the b subtree only weighs 0.5.
+        simulation.validators[0][0].received_blocks.remove(&b2_bis);
+        assert_eq!(simulation.choose_head(0, 0, b2, &weight(0.5)), None);
 
-    let hash_max = Float::with_val(FLOAT_PRECISION, 2).pow(256);
+        let b3 = simulation.insert_block(
+            0,
+            Block {
+                height: 3,
+                shard_id: 0,
+                previous_block_id: b2,
+                validator_id: 6,
+                weight: weight(0.2),
+                time: 0,
+            },
+            0,
+        );
+        simulation.validators[0][0].received_blocks.insert(b3);
+        assert_eq!(simulation.choose_head(0, 0, b3, &weight(0.7)), Some(b3));
 
-    let stake: Float = stake / hash_max;
-    let stake: Float = stake * 10;
-    let stake = stake.pow(spread_factor);
+        // Blocks not received are never followed, whatever their descendants.
+        simulation.validators[0][0].received_blocks.remove(&b);
+        assert_eq!(simulation.choose_head(0, 0, b3, &weight(0.7)), None);
+    }
+
+    #[test]
+    fn shard_stalls_once_no_pending_event_can_create_a_block() {
+        let (mut simulation, [a, a2, _, _, _]) = fork(ForkChoice::FullSum);
+        simulation.event_pool.clear();
+        assert!(simulation.shard_stalled(0));
+
+        let vdf = |input_block_id, output_block_height| TimedEvent {
+            time: 0,
+            validator_id: 0,
+            event: Event::VdfFinished {
+                shard_id: 0,
+                input_block_id,
+                output_block_height,
+                weight: weight(0.1),
+            },
+        };
 
-    1 + stake
+        // Validator 0 follows a2 : the VDF of a can build height 3 on it, the
+        // one of a2 waits for a child of a2.
+        simulation.event_pool.push(vdf(a2, 4));
+        assert!(simulation.shard_stalled(0));
+
+        simulation.event_pool.push(vdf(a, 3));
+        assert!(!simulation.shard_stalled(0));
+
+        simulation.validators[0][0].latest_created_height = 3;
+        assert!(simulation.shard_stalled(0));
+
+        simulation.event_pool.push(TimedEvent {
+            time: 0,
+            validator_id: 1,
+            event: Event::BlockReceived {
+                shard_id: 0,
+                block_id: a2,
+            },
+        });
+        assert!(!simulation.shard_stalled(0));
+    }
+
+    #[test]
+    fn epoch_seed_falls_back_below_unfinalized_height() {
+        let (mut simulation, [a, a2, ..]) = fork(ForkChoice::FullSum);
+        simulation.config.epoch_seed = EpochSeed::FinalizedBlock;
+        let seed_height = simulation.seed_height(2).unwrap();
+
+        assert_eq!(simulation.epoch_seed(2), b"seed".to_vec());
+
+        simulation.finalized_blocks[0].insert(1, a);
+        assert_eq!(simulation.epoch_seed(2), simulation.block_seed(a));
+
+        simulation.finalized_blocks[0].insert(seed_height, a2);
+        assert_eq!(simulation.epoch_seed(2), simulation.block_seed(a2));
+    }
+
+    #[test]
+    fn block_seed_depends_on_block_content() {
+        let block = Block {
+            height: 1,
+            shard_id: 0,
+            previous_block_id: 0,
+            validator_id: 3,
+            weight: weight(0.5),
+            time: 10,
+        };
+
+        let mut first = Simulation::new(config());
+        let mut second = Simulation::new(config());
+        let first_id = first.insert_block(10, block.clone(), 0);
+        let second_id = second.insert_block(10, block, 1);
+
+        assert_eq!(first_id, second_id);
+        assert_ne!(first.block_seed(first_id), second.block_seed(second_id));
+    }
+
+    #[test]
+    fn slashing_applies_from_next_epoch() {
+        let mut config = config();
+        config.behaviours = vec![(1, BehaviourConfig::Equivocating)]
+            .into_iter()
+            .collect();
+        config.slashing_penalty = Some(0.5);
+
+        let simulation = run(config);
+        let slashing: Vec<_> = simulation
+            .stake_changes
+            .iter()
+            .filter(|c| c.slashing)
+            .collect();
+
+        assert!(!simulation.slashing_evidences.is_empty());
+        assert_eq!(slashing.len(), 1);
+        assert_eq!(slashing[0].validator_id, 1);
+        assert!(slashing[0].epoch >= 1);
+
+        let before = simulation.compute_epoch_powers(slashing[0].epoch - 1);
+        let after = simulation.compute_epoch_powers(slashing[0].epoch);
+
+        assert_eq!(before, simulation.initial_stakes);
+
+        let total = Float::with_val(FLOAT_PRECISION, Float::sum(after.iter()));
+        assert!((total - 1.0f64).abs() < 1e-12);
+
+        // The slashed validator loses half its stake, the others keep their
+        // relative powers.
+        let ratio = |powers: &[Float]| Float::with_val(FLOAT_PRECISION, &powers[1] / &powers[0]);
+        let expected: Float = ratio(&before) * 0.5;
+        assert!((ratio(&after) - expected).abs() < 1e-12);
+        assert_eq!(
+            Float::with_val(FLOAT_PRECISION, &before[2] / &before[0]),
+            Float::with_val(FLOAT_PRECISION, &after[2] / &after[0])
+        );
+
+        // Cached powers of past epochs are kept, later ones use the penalty.
+        for (epoch, powers) in &simulation.epoch_powers {
+            assert_eq!(*powers, simulation.compute_epoch_powers(*epoch));
+        }
+    }
 }
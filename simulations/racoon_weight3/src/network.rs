@@ -0,0 +1,429 @@
+//! Network model : delay of a message between 2 validators.
+
+use racoon_core::rng;
+use serde::Deserialize;
+
+/// Distribution of the base latency of each link between 2 validators.
+/// It is drawn once per link.
+#[derive(Clone, Debug, Deserialize)]
+pub enum Latency {
+    /// Same latency on every link.
+    Constant(u64),
+    /// Latency uniformly distributed between `min` and `max` ticks.
+    Uniform { min: u64, max: u64 },
+    /// Log-normal latency : `median * exp(sigma * N(0, 1))`.
+    LogNormal { median: u64, sigma: f64 },
+    /// Validators are spread over regions (validator `i` is in region
+    /// `i % regions`), `matrix[a][b]` being the latency from region `a` to
+    /// region `b`.
+    Regions { matrix: Vec<Vec<u64>> },
+}
+
+/// Network config.
+#[derive(Clone, Debug, Deserialize)]
+pub struct NetworkConfig {
+    /// Base latency of links.
+    pub latency: Latency,
+    /// Max number of ticks randomly added to each message.
+    pub jitter_ticks: u64,
+    /// Size of a block in bytes.
+    pub block_size: u64,
+    /// Bytes transmitted per tick by a link. None is an unlimited bandwidth.
+    pub bandwidth: Option<f64>,
+    /// Scheduled partitions.
+    pub partitions: Vec<Partition>,
+    /// Probability for a message to be lost.
+    pub drop_rate: f64,
+    /// Probability for a message to be delivered twice.
+    pub duplicate_rate: f64,
+}
+
+/// Validators split into groups which can't communicate during a time interval.
+/// Messages sent between groups during the partition are lost.
+#[derive(Clone, Debug, Deserialize)]
+pub struct Partition {
+    /// Tick at which the partition starts.
+    pub start: u64,
+    /// Tick at which the partition heals.
+    pub end: u64,
+    /// Validators ids of each group. Validators not listed form an extra group.
+    pub groups: Vec<Vec<usize>>,
+}
+
+impl Partition {
+    /// Group of a validator.
+    fn group(&self, validator: usize) -> usize {
+        self.groups
+            .iter()
+            .position(|group| group.contains(&validator))
+            .unwrap_or(self.groups.len())
+    }
+
+    /// Check if the partition prevents a message sent at given time to go
+    /// from a validator to another one.
+    pub fn separates(&self, from: usize, to: usize, time: u64) -> bool {
+        time >= self.start && time < self.end && self.group(from) != self.group(to)
+    }
+}
+
+/// Network between validators.
+#[derive(Clone, Debug)]
+pub struct Network {
+    config: NetworkConfig,
+    /// Base latency from validator `i` to validator `j` at `i * validators + j`.
+    links: Vec<u64>,
+    validators: usize,
+}
+
+impl Network {
+    /// Draw links latencies between given amount of validators.
+    pub fn new(config: NetworkConfig, validators: usize) -> Self {
+        config.validate();
+
+        let mut links = vec![0; validators * validators];
+
+        for from in 0..validators {
+            for to in 0..validators {
+                if from != to {
+                    links[from * validators + to] = config.latency.draw(from, to);
+                }
+            }
+        }
+
+        Self {
+            config,
+            links,
+            validators,
+        }
+    }
+
+    /// Scheduled partitions.
+    pub fn partitions(&self) -> &[Partition] {
+        &self.config.partitions
+    }
+
+    /// Delays after which a block sent at given time from a validator is
+    /// received by another one : none if the message is lost, 2 if it is
+    /// duplicated.
+    /// A validator receives its own blocks instantly.
+    pub fn block_deliveries(&self, from: usize, to: usize, block_id: u64, time: u64) -> Vec<u64> {
+        if from == to {
+            return vec![0];
+        }
+
+        let message = [from as u64, to as u64, block_id];
+
+        if self
+            .config
+            .partitions
+            .iter()
+            .any(|p| p.separates(from, to, time))
+        {
+            return vec![];
+        }
+
+        if rng::uniform(b"drop", &message) < self.config.drop_rate {
+            return vec![];
+        }
+
+        let mut deliveries = vec![self.block_delay(from, to, block_id, 0)];
+
+        if rng::uniform(b"duplicate", &message) < self.config.duplicate_rate {
+            deliveries.push(self.block_delay(from, to, block_id, 1));
+        }
+
+        deliveries
+    }
+
+    /// Number of ticks a copy of a block takes to go from a validator to
+    /// another one.
+    fn block_delay(&self, from: usize, to: usize, block_id: u64, copy: u64) -> u64 {
+        let latency = self.links[from * self.validators + to];
+
+        let jitter = if self.config.jitter_ticks > 0 {
+            let u = rng::uniform(b"jitter", &[from as u64, to as u64, block_id, copy]);
+            (u * (self.config.jitter_ticks + 1) as f64) as u64
+        } else {
+            0
+        };
+
+        let transfer = match self.config.bandwidth {
+            Some(bandwidth) => (self.config.block_size as f64 / bandwidth).ceil() as u64,
+            None => 0,
+        };
+
+        latency.saturating_add(jitter).saturating_add(transfer)
+    }
+}
+
+impl NetworkConfig {
+    /// Check that delays can be computed and rates are probabilities.
+    fn validate(&self) {
+        self.latency.validate();
+
+        if let Some(bandwidth) = self.bandwidth {
+            assert!(
+                bandwidth > 0.0,
+                "bandwidth ({}) must be positive",
+                bandwidth
+            );
+        }
+
+        for (name, rate) in &[
+            ("drop_rate", self.drop_rate),
+            ("duplicate_rate", self.duplicate_rate),
+        ] {
+            assert!(
+                (0.0..=1.0).contains(rate),
+                "{} ({}) must be in [0;1]",
+                name,
+                rate
+            );
+        }
+    }
+}
+
+impl Latency {
+    /// Check that latencies can be drawn from the distribution.
+    fn validate(&self) {
+        match self {
+            Latency::Uniform { min, max } => assert!(
+                min <= max,
+                "uniform latency min ({}) must not exceed max ({})",
+                min,
+                max
+            ),
+            Latency::LogNormal { sigma, .. } => assert!(
+                *sigma >= 0.0,
+                "log-normal latency sigma ({}) must not be negative",
+                sigma
+            ),
+            Latency::Regions { matrix } => {
+                assert!(!matrix.is_empty(), "regions latency matrix is empty");
+
+                for (region, row) in matrix.iter().enumerate() {
+                    assert!(
+                        row.len() == matrix.len(),
+                        "regions latency matrix must be square : row {} has {} columns for {} regions",
+                        region,
+                        row.len(),
+                        matrix.len()
+                    );
+                }
+            }
+            Latency::Constant(_) => {}
+        }
+    }
+
+    /// Draw the base latency of the link from a validator to another one.
+    fn draw(&self, from: usize, to: usize) -> u64 {
+        let link = [from as u64, to as u64];
+
+        match self {
+            Latency::Constant(ticks) => *ticks,
+            Latency::Uniform { min, max } => {
+                let u = rng::uniform(b"latency", &link);
+                let span = max - min;
+                min + ((u * (span as f64 + 1.0)) as u64).min(span)
+            }
+            Latency::LogNormal { median, sigma } => {
+                let z = rng::normal(b"latency", &link);
+                (*median as f64 * (sigma * z).exp()).round() as u64
+            }
+            Latency::Regions { matrix } => {
+                let regions = matrix.len();
+                matrix[from % regions][to % regions]
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(latency: Latency) -> NetworkConfig {
+        NetworkConfig {
+            latency,
+            jitter_ticks: 0,
+            block_size: 1000,
+            bandwidth: None,
+            partitions: vec![],
+            drop_rate: 0.0,
+            duplicate_rate: 0.0,
+        }
+    }
+
+    #[test]
+    fn partition_separates_groups_during_interval() {
+        let partition = Partition {
+            start: 10,
+            end: 20,
+            groups: vec![vec![0, 1], vec![2]],
+        };
+
+        assert!(partition.separates(0, 2, 10));
+        assert!(partition.separates(2, 1, 19));
+        assert!(!partition.separates(0, 2, 9));
+        assert!(!partition.separates(0, 2, 20));
+        assert!(!partition.separates(0, 1, 15));
+    }
+
+    #[test]
+    fn unlisted_validators_form_extra_group() {
+        let partition = Partition {
+            start: 0,
+            end: 10,
+            groups: vec![vec![0, 1], vec![2]],
+        };
+
+        assert!(partition.separates(0, 3, 5));
+        assert!(partition.separates(4, 2, 5));
+        assert!(!partition.separates(3, 4, 5));
+    }
+
+    #[test]
+    fn own_blocks_are_received_instantly() {
+        let mut config = config(Latency::Constant(100));
+        config.drop_rate = 1.0;
+        let network = Network::new(config, 4);
+
+        assert_eq!(network.block_deliveries(2, 2, 7, 0), vec![0]);
+    }
+
+    #[test]
+    fn drop_rate_one_loses_every_block() {
+        let mut config = config(Latency::Constant(100));
+        config.drop_rate = 1.0;
+        let network = Network::new(config, 4);
+
+        for block_id in 0..100 {
+            assert!(network.block_deliveries(0, 1, block_id, 0).is_empty());
+        }
+    }
+
+    #[test]
+    fn duplicate_rate_one_delivers_every_block_twice() {
+        let mut config = config(Latency::Constant(100));
+        config.duplicate_rate = 1.0;
+        let network = Network::new(config, 4);
+
+        for block_id in 0..100 {
+            assert_eq!(network.block_deliveries(0, 1, block_id, 0), vec![100, 100]);
+        }
+    }
+
+    #[test]
+    fn jitter_stays_within_bound() {
+        let mut config = config(Latency::Constant(100));
+        config.jitter_ticks = 50;
+        let network = Network::new(config, 4);
+
+        let delays: Vec<_> = (0..1000)
+            .flat_map(|block_id| network.block_deliveries(0, 1, block_id, 0))
+            .collect();
+
+        assert!(delays.iter().all(|delay| (100..=150).contains(delay)));
+        assert!(delays.iter().any(|delay| *delay != delays[0]));
+    }
+
+    #[test]
+    fn regions_use_validator_modulo() {
+        let latency = Latency::Regions {
+            matrix: vec![vec![1, 2], vec![3, 4]],
+        };
+
+        assert_eq!(latency.draw(0, 1), 2);
+        assert_eq!(latency.draw(2, 1), 2);
+        assert_eq!(latency.draw(3, 2), 3);
+        assert_eq!(latency.draw(5, 7), 4);
+    }
+
+    #[test]
+    fn uniform_latency_stays_in_range() {
+        let latency = Latency::Uniform { min: 10, max: 20 };
+
+        for from in 0..100 {
+            assert!((10..=20).contains(&latency.draw(from, 0)));
+        }
+
+        assert_eq!(Latency::Uniform { min: 5, max: 5 }.draw(1, 2), 5);
+        Latency::Uniform {
+            min: 0,
+            max: u64::MAX,
+        }
+        .draw(1, 2);
+    }
+
+    #[test]
+    fn tiny_bandwidth_saturates_delay() {
+        let mut config = config(Latency::Constant(100));
+        config.bandwidth = Some(f64::MIN_POSITIVE);
+        let network = Network::new(config, 4);
+
+        assert_eq!(network.block_deliveries(0, 1, 0, 0), vec![u64::MAX]);
+    }
+
+    #[test]
+    #[should_panic(expected = "bandwidth (0) must be positive")]
+    fn bandwidth_must_not_be_null() {
+        let mut config = config(Latency::Constant(100));
+        config.bandwidth = Some(0.0);
+        config.validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "bandwidth (-1) must be positive")]
+    fn bandwidth_must_not_be_negative() {
+        let mut config = config(Latency::Constant(100));
+        config.bandwidth = Some(-1.0);
+        config.validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "drop_rate (1.5) must be in [0;1]")]
+    fn drop_rate_must_be_a_probability() {
+        let mut config = config(Latency::Constant(100));
+        config.drop_rate = 1.5;
+        config.validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "duplicate_rate (-0.1) must be in [0;1]")]
+    fn duplicate_rate_must_be_a_probability() {
+        let mut config = config(Latency::Constant(100));
+        config.duplicate_rate = -0.1;
+        config.validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "must not exceed max")]
+    fn uniform_min_must_not_exceed_max() {
+        Latency::Uniform { min: 2, max: 1 }.validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "must not be negative")]
+    fn log_normal_sigma_must_not_be_negative() {
+        Latency::LogNormal {
+            median: 100,
+            sigma: -1.0,
+        }
+        .validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "matrix is empty")]
+    fn regions_matrix_must_not_be_empty() {
+        Latency::Regions { matrix: vec![] }.validate();
+    }
+
+    #[test]
+    #[should_panic(expected = "must be square")]
+    fn regions_matrix_must_be_square() {
+        Latency::Regions {
+            matrix: vec![vec![1, 2], vec![3]],
+        }
+        .validate();
+    }
+}
@@ -0,0 +1,608 @@
+//! Statistics printed at the end of a simulation, and latencies written for
+//! the topology simulation of racoon_weight.
+
+use crate::{EpochSeed, Simulation};
+use rug::Float;
+use serde::Serialize;
+use std::collections::{BTreeMap, BTreeSet};
+use tracing::instrument;
+
+/// Latencies of a finalized block, in ticks.
+#[derive(Serialize)]
+struct BlockLatency {
+    shard: usize,
+    height: u64,
+    /// From the creation of the block to its finalization.
+    finality: u64,
+    /// From the finalization of the block to the finalization of the first
+    /// beacon block cross-linking it, none if it never was.
+    crosslink: Option<u64>,
+}
+
+impl Simulation {
+    #[instrument(skip(self))]
+    pub fn print_stats(&self) {
+        self.progress.finish();
+
+        println!("Fork choice : {:?}", self.config.fork_choice);
+
+        for shard in 0..self.validators.len() {
+            if shard == 0 && self.config.crosslink_period.is_some() {
+                println!("Beacon chain :");
+            } else if self.validators.len() > 1 {
+                println!("Shard {} :", shard);
+            }
+
+            self.print_fairness(shard);
+            self.print_average_time(shard);
+            self.print_finality(shard);
+        }
+
+        self.print_epochs();
+        self.print_stake_changes();
+        self.print_multi_shard_wins();
+        self.print_crosslinks();
+        self.print_faults();
+        self.print_long_range_attack();
+        self.print_revenue();
+    }
+
+    fn print_revenue(&self) {
+        // strategy -> (validators, power, finalized blocks)
+        let mut strategies = BTreeMap::new();
+        let finalized_count: usize = self.finalized_blocks.iter().map(|f| f.len()).sum();
+
+        // Power of each validator, averaged over the finalized blocks of all
+        // shards.
+        let mut powers = vec![0.0; self.config.validators_count];
+        for (shard, finalized_blocks) in self.finalized_blocks.iter().enumerate() {
+            let share = finalized_blocks.len() as f64 / finalized_count as f64;
+
+            for (power, shard_power) in powers.iter_mut().zip(self.expected_powers(shard)) {
+                *power += shard_power * share;
+            }
+        }
+
+        for (validator, power) in self.validators[0].iter().zip(powers) {
+            let entry = strategies
+                .entry(validator.behaviour.name())
+                .or_insert((0, 0.0, 0));
+            entry.0 += 1;
+            entry.1 += power;
+        }
+
+        for block_id in self.finalized_blocks.iter().flat_map(|f| f.values()) {
+            let validator_id = self.blocks[block_id].validator_id;
+            strategies
+                .get_mut(self.validators[0][validator_id].behaviour.name())
+                .unwrap()
+                .2 += 1;
+        }
+
+        println!("strategy        validators  power       revenue     revenue/power");
+
+        for (name, (validators, power, wins)) in strategies {
+            let revenue = wins as f64 / finalized_count as f64;
+            println!(
+                "{:<15} {:>10}  {:0.8}  {:0.8}  {:0.4}",
+                name,
+                validators,
+                power,
+                revenue,
+                revenue / power
+            );
+        }
+    }
+
+    fn print_faults(&self) {
+        println!("Dropped messages : {}", self.dropped_messages);
+        println!("Duplicated messages : {}", self.duplicated_messages);
+
+        match self.divergence {
+            Some((time, height)) => println!(
+                "Finalization divergence : at height {} (time {})",
+                height, time
+            ),
+            None => println!("Finalization divergence : none"),
+        }
+
+        match self.stall {
+            Some((time, shard_id, height)) => println!(
+                "Stall : shard {} at height {} (time {})",
+                shard_id, height, time
+            ),
+            None => println!("Stall : none"),
+        }
+
+        for (i, partition) in self.network.partitions().iter().enumerate() {
+            match self.reconvergence[i] {
+                Some(time) => println!(
+                    "Partition {} reconvergence time : {}",
+                    i,
+                    time - partition.end
+                ),
+                None => println!("Partition {} reconvergence time : never", i),
+            }
+        }
+
+        println!("Slashing evidences : {}", self.slashing_evidences.len());
+
+        let mut evidences_per_validator = BTreeMap::new();
+        for evidence in &self.slashing_evidences {
+            *evidences_per_validator
+                .entry(evidence.validator_id)
+                .or_insert(0) += 1;
+        }
+
+        for (validator_id, count) in evidences_per_validator {
+            let first = self
+                .slashing_evidences
+                .iter()
+                .find(|e| e.validator_id == validator_id)
+                .unwrap();
+
+            println!(
+                "Validator {} : {} evidences, first at height {} (shard {}, time {}, blocks {:?}, parents {:?}){}",
+                validator_id,
+                count,
+                first.height,
+                first.shard_id,
+                first.time,
+                first.block_ids,
+                first.previous_block_ids,
+                if self.slashed.contains(&validator_id) {
+                    ", slashed"
+                } else {
+                    ""
+                }
+            );
+        }
+
+        // Blocks up to the last finalized height that are not in the finalized chain.
+        let mut orphaned = vec![];
+
+        for (shard_id, finalized_blocks) in self.finalized_blocks.iter().enumerate() {
+            if let Some((&last_height, &last_id)) = finalized_blocks.iter().next_back() {
+                let mut canonical = BTreeSet::new();
+                let mut block_id = last_id;
+
+                while block_id != 0 {
+                    canonical.insert(block_id);
+                    block_id = self.blocks[&block_id].previous_block_id;
+                }
+
+                orphaned.extend(
+                    self.blocks
+                        .iter()
+                        .filter(|(id, block)| {
+                            block.shard_id == shard_id as u64
+                                && block.height <= last_height
+                                && !canonical.contains(id)
+                        })
+                        .map(|(id, _)| id),
+                );
+            }
+        }
+
+        if self.finalized_blocks.iter().any(|f| !f.is_empty()) {
+            let reorged = orphaned
+                .iter()
+                .filter(|id| self.accepted_heads.contains(id))
+                .count();
+
+            println!("Orphaned blocks : {}", orphaned.len());
+            println!("Orphaned blocks once accepted as head : {}", reorged);
+        }
+    }
+
+    /// Time between the first finalization of shard blocks and the beacon
+    /// blocks including them, for the finalized beacon chain.
+    fn print_crosslinks(&self) {
+        if self.config.crosslink_period.is_none() {
+            return;
+        }
+
+        // shard -> (inclusion latencies, beacon finalization latencies)
+        let mut latencies = vec![(vec![], vec![]); self.validators.len()];
+        let mut previous = vec![0; self.validators.len()];
+
+        for (beacon_height, beacon_block_id) in &self.finalized_blocks[0] {
+            let crosslinks = self.parent_crosslinks(*beacon_block_id);
+            let beacon_block = &self.blocks[beacon_block_id];
+            let beacon_finalization = self.finalization_times[0][beacon_height];
+
+            for shard in 1..self.validators.len() {
+                let shard_block_id = crosslinks[shard];
+
+                if shard_block_id != 0 && shard_block_id != previous[shard] {
+                    let shard_height = self.block_height(shard_block_id);
+                    let shard_finalization = self.finalization_times[shard][&shard_height];
+
+                    latencies[shard]
+                        .0
+                        .push(beacon_block.time - shard_finalization);
+                    latencies[shard]
+                        .1
+                        .push(beacon_finalization - shard_finalization);
+                }
+            }
+
+            previous = crosslinks;
+        }
+
+        for (shard, (included, finalized)) in latencies.iter().enumerate().skip(1) {
+            let average = |l: &Vec<u64>| l.iter().sum::<u64>() as f64 / l.len() as f64;
+
+            println!("Shard {} cross-links : {}", shard, included.len());
+            println!(
+                "Shard {} average cross-link latency : {:.1} (beacon finalized : {:.1})",
+                shard,
+                average(included),
+                average(finalized)
+            );
+            println!(
+                "Shard {} max cross-link latency : {} (beacon finalized : {})",
+                shard,
+                included.iter().max().unwrap_or(&0),
+                finalized.iter().max().unwrap_or(&0)
+            );
+        }
+    }
+
+    /// Write the latencies of each finalized block to a CSV file.
+    pub fn write_latencies(&self, path: &str) {
+        let mut writer =
+            csv::Writer::from_path(path).unwrap_or_else(|e| panic!("cannot write {}: {}", path, e));
+
+        for (shard, finalized_blocks) in self.finalized_blocks.iter().enumerate() {
+            let crosslink_finalizations = self.crosslink_finalizations(shard);
+
+            for (&height, block_id) in finalized_blocks {
+                let finalization = self.finalization_times[shard][&height];
+
+                writer
+                    .serialize(BlockLatency {
+                        shard,
+                        height,
+                        finality: finalization - self.blocks[block_id].time,
+                        crosslink: crosslink_finalizations
+                            .get(&height)
+                            .map(|time| time.saturating_sub(finalization)),
+                    })
+                    .unwrap();
+            }
+        }
+
+        writer.flush().unwrap();
+        println!("Latencies written to {}", path);
+    }
+
+    /// Time at which each height of a shard is first covered by a cross-link
+    /// finalized on the beacon chain.
+    fn crosslink_finalizations(&self, shard: usize) -> BTreeMap<u64, u64> {
+        let mut times = BTreeMap::new();
+
+        if shard == 0 || self.config.crosslink_period.is_none() {
+            return times;
+        }
+
+        let mut covered_height = 0;
+
+        for (beacon_height, beacon_block_id) in &self.finalized_blocks[0] {
+            let shard_block_id = self.parent_crosslinks(*beacon_block_id)[shard];
+
+            if shard_block_id == 0 {
+                continue;
+            }
+
+            let shard_height = self.block_height(shard_block_id);
+
+            for height in covered_height + 1..=shard_height {
+                times.insert(height, self.finalization_times[0][beacon_height]);
+            }
+
+            covered_height = covered_height.max(shard_height);
+        }
+
+        times
+    }
+
+    fn print_epochs(&self) {
+        let heights = self.finalized_blocks[0].keys().last().copied().unwrap_or(0);
+
+        println!("Epoch seed : {:?}", self.config.epoch_seed);
+        println!("Epochs : {}", heights / self.config.heights_per_epoch + 1);
+
+        if let EpochSeed::Fixed = self.config.epoch_seed {
+            return;
+        }
+
+        println!(
+            "Epoch seed checks : {} (mismatches : {}, unverified : {})",
+            self.seed_checks, self.seed_mismatches, self.unverified_seeds
+        );
+        println!(
+            "VDF skipped, epoch seed not finalized : {}",
+            self.unavailable_seeds
+        );
+    }
+
+    fn print_stake_changes(&self) {
+        if self.stake_changes.is_empty() && self.config.stake_grinding.is_none() {
+            return;
+        }
+
+        println!(
+            "Stake changes : {} (delay : {} heights)",
+            self.stake_changes.len(),
+            self.config.stake_change_delay
+        );
+
+        for change in &self.stake_changes {
+            println!(
+                "{} : validator {} {:+.6} at height {}, from epoch {}",
+                if change.slashing {
+                    "Slashing"
+                } else {
+                    "Stake change"
+                },
+                change.validator_id,
+                change.amount,
+                change.height,
+                change.epoch
+            );
+        }
+
+        let coalition = match &self.config.stake_grinding {
+            Some(coalition) => coalition,
+            None => return,
+        };
+
+        let powers = self.expected_powers(0);
+        let expected: f64 = coalition.iter().map(|&v| powers[v]).sum();
+        let wins = self.finalized_blocks[0]
+            .values()
+            .filter(|id| coalition.contains(&self.blocks[id].validator_id))
+            .count();
+
+        println!(
+            "Grinding coalition : {} epochs ground, power {:.6}, finalized share {:.6}",
+            self.ground_epochs.len(),
+            expected,
+            wins as f64 / self.finalized_blocks[0].len() as f64
+        );
+    }
+
+    /// Maximum amount of shards on which the same validator won at the same
+    /// height, the beacon chain excluded.
+    fn print_multi_shard_wins(&self) {
+        let (first_shard, _) = self.assigned_shards();
+
+        // (height, validator) -> shards won
+        let mut wins = BTreeMap::new();
+
+        for finalized_blocks in &self.finalized_blocks[first_shard as usize..] {
+            for (&height, block_id) in finalized_blocks {
+                *wins
+                    .entry((height, self.blocks[block_id].validator_id))
+                    .or_insert(0) += 1;
+            }
+        }
+
+        println!(
+            "Max multi shard win : {}",
+            wins.values().max().unwrap_or(&0)
+        );
+    }
+
+    fn print_long_range_attack(&self) {
+        let attack = match &self.config.long_range_attack {
+            Some(attack) => attack,
+            None => return,
+        };
+
+        if self.attack_blocks.is_empty() {
+            println!(
+                "Long-range attack : not published (no block finalized at height {} at time {})",
+                attack.fork_height, attack.publish_time
+            );
+            return;
+        }
+
+        let top_height = self.block_height(*self.attack_blocks.iter().next_back().unwrap());
+
+        println!(
+            "Long-range attack : {} blocks from height {} to {} (honest height {})",
+            self.attack_blocks.len(),
+            attack.fork_height,
+            top_height,
+            self.attack_honest_height
+        );
+
+        let finalized = self.finalized_blocks[0]
+            .values()
+            .filter(|id| self.attack_blocks.contains(id))
+            .count();
+
+        println!("Attack blocks finalized : {}", finalized);
+        println!(
+            "Attack blocks refused below finalized block : {}",
+            self.attack_refusals
+        );
+        println!(
+            "Honest validators which followed the attack fork : {}",
+            self.attack_followers.len()
+        );
+    }
+
+    fn print_fairness(&self, shard: usize) {
+        let finalized_blocks = &self.finalized_blocks[shard];
+        let mut validators_wins = vec![0; self.config.validators_count];
+
+        for block_id in finalized_blocks.values() {
+            let block = &self.blocks[block_id];
+            validators_wins[block.validator_id] += 1;
+        }
+
+        let mut diff_sum = 0.0;
+
+        for (wins, power) in validators_wins.iter().zip(self.expected_powers(shard)) {
+            let winrate = *wins as f64 / finalized_blocks.len() as f64;
+            let diff = winrate - power;
+            diff_sum += diff.abs();
+        }
+
+        let fairness = diff_sum / self.config.validators_count as f64;
+
+        println!("Fairness : {:.9}", fairness);
+    }
+
+    /// Power of each validator on a shard, averaged over its finalized
+    /// heights.
+    pub fn expected_powers(&self, shard: usize) -> Vec<f64> {
+        let finalized_blocks = &self.finalized_blocks[shard];
+
+        // epoch -> finalized heights
+        let mut epoch_heights = BTreeMap::new();
+        for height in finalized_blocks.keys() {
+            *epoch_heights
+                .entry(height / self.config.heights_per_epoch)
+                .or_insert(0) += 1;
+        }
+
+        let mut powers = vec![0.0; self.config.validators_count];
+
+        for (epoch, heights) in epoch_heights {
+            let share = heights as f64 / finalized_blocks.len() as f64;
+
+            for (power, epoch_power) in powers.iter_mut().zip(self.epoch_shard_powers(shard, epoch))
+            {
+                *power += epoch_power * share;
+            }
+        }
+
+        powers
+    }
+
+    /// Power of each validator relative to the validators assigned to a shard
+    /// during an epoch, like `shard_power`.
+    pub fn epoch_shard_powers(&self, shard: usize, epoch: u64) -> Vec<f64> {
+        let mut powers: Vec<_> = if self.stake_changes.is_empty() {
+            self.validators[shard]
+                .iter()
+                .map(|v| v.power.to_f64())
+                .collect()
+        } else {
+            self.compute_epoch_powers(epoch)
+                .iter()
+                .map(Float::to_f64)
+                .collect()
+        };
+
+        if self.runs_shard(shard as u64) {
+            return powers;
+        }
+
+        for (power, shards) in powers.iter_mut().zip(&self.shard_assignments[&epoch]) {
+            if !shards.contains(&(shard as u64)) {
+                *power = 0.0;
+            }
+        }
+
+        let total: f64 = powers.iter().sum();
+        powers.iter().map(|power| power / total).collect()
+    }
+
+    fn print_finality(&self, shard: usize) {
+        let latencies: Vec<_> = self.finalization_times[shard]
+            .iter()
+            .map(|(height, time)| time - self.blocks[&self.finalized_blocks[shard][height]].time)
+            .collect();
+
+        let average_latency = latencies.iter().sum::<u64>() as f64 / latencies.len() as f64;
+
+        println!("Average finality latency : {:.1}", average_latency);
+        println!(
+            "Max finality latency : {}",
+            latencies.iter().max().unwrap_or(&0)
+        );
+
+        let reorg_depths = &self.reorg_depths[shard];
+        let average_depth = reorg_depths.iter().sum::<u64>() as f64 / reorg_depths.len() as f64;
+
+        println!("Reorgs : {}", reorg_depths.len());
+        println!("Average reorg depth : {:.2}", average_depth);
+        println!(
+            "Max reorg depth : {}",
+            reorg_depths.iter().max().unwrap_or(&0)
+        );
+    }
+
+    fn print_average_time(&self, shard: usize) {
+        let finalized_blocks = &self.finalized_blocks[shard];
+
+        if finalized_blocks.len() < 2 {
+            println!(
+                "Average block time : none ({} finalized blocks)",
+                finalized_blocks.len()
+            );
+            return;
+        }
+
+        let mut diff_sum = 0.0;
+        let mut diff_min = u64::MAX;
+        let mut diff_max = 0;
+
+        let mut diff_odd_even_sum = 0.0;
+        let mut diff_odd_even_min = u64::MAX;
+        let mut diff_odd_even_max = 0;
+
+        let mut diff_even_odd_sum = 0.0;
+        let mut diff_even_odd_min = u64::MAX;
+        let mut diff_even_odd_max = 0;
+
+        let mut odd_even_count = 0;
+        let mut even_odd_count = 0;
+
+        let mut iter = finalized_blocks.iter().peekable();
+
+        while let Some((height, block_id)) = iter.next() {
+            if let Some((_, next_block_id)) = iter.peek() {
+                let block0 = &self.blocks[block_id];
+                let block1 = &self.blocks[next_block_id];
+                let diff = block1.time - block0.time;
+                diff_sum += diff as f64;
+                diff_min = std::cmp::min(diff_min, diff);
+                diff_max = std::cmp::max(diff_max, diff);
+
+                if height % 2 == 0 {
+                    diff_even_odd_sum += diff as f64;
+                    diff_even_odd_min = std::cmp::min(diff_even_odd_min, diff);
+                    diff_even_odd_max = std::cmp::max(diff_even_odd_max, diff);
+                    even_odd_count += 1;
+                } else {
+                    diff_odd_even_sum += diff as f64;
+                    diff_odd_even_min = std::cmp::min(diff_odd_even_min, diff);
+                    diff_odd_even_max = std::cmp::max(diff_odd_even_max, diff);
+                    odd_even_count += 1;
+                }
+            }
+        }
+
+        let average_time = diff_sum / (finalized_blocks.len() - 1) as f64;
+        let average_even_odd = diff_even_odd_sum / even_odd_count as f64;
+        let average_odd_even = diff_odd_even_sum / odd_even_count as f64;
+
+        println!("Average block time : {:.1}", average_time);
+        println!("Min block time : {}", diff_min);
+        println!("Max block time : {}", diff_max);
+
+        println!("Average even-odd block time : {:.1}", average_even_odd);
+        println!("Min even-odd block time : {}", diff_even_odd_min);
+        println!("Max even-odd block time : {}", diff_even_odd_max);
+
+        println!("Average odd-even block time : {:.1}", average_odd_even);
+        println!("Min odd-even block time : {}", diff_odd_even_min);
+        println!("Max odd-even block time : {}", diff_odd_even_max);
+    }
+}
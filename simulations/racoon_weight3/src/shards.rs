@@ -0,0 +1,105 @@
+//! Assignment of validators to shards.
+
+use crate::Simulation;
+use racoon_core::{rng, FLOAT_PRECISION};
+use rug::Float;
+use std::collections::BTreeSet;
+
+impl Simulation {
+    /// First shard and amount of shards validators are assigned to. The
+    /// beacon chain is run by all validators.
+    pub fn assigned_shards(&self) -> (u64, u64) {
+        match self.config.crosslink_period {
+            Some(_) => (1, self.config.shards - 1),
+            None => (0, self.config.shards),
+        }
+    }
+
+    /// Check if a validator runs every shard it follows.
+    pub fn runs_shard(&self, shard_id: u64) -> bool {
+        let (first_shard, shards) = self.assigned_shards();
+        shard_id < first_shard || self.config.shards_per_validator >= shards
+    }
+
+    /// Shards of each validator during the epoch of given height.
+    fn shard_assignment(&mut self, height: u64) -> &[BTreeSet<u64>] {
+        let epoch = height / self.config.heights_per_epoch;
+        let (first_shard, shards) = self.assigned_shards();
+        let seed = self.epoch_seed(epoch);
+        let config = &self.config;
+
+        self.shard_assignments.entry(epoch).or_insert_with(|| {
+            assign_shards(
+                &seed,
+                epoch,
+                config.validators_count,
+                first_shard,
+                shards,
+                config.shards_per_validator,
+            )
+        })
+    }
+
+    /// Check if a validator produces blocks on a shard at given height.
+    pub fn is_assigned(&mut self, shard_id: u64, validator_id: usize, height: u64) -> bool {
+        if self.runs_shard(shard_id) {
+            return true;
+        }
+
+        self.shard_assignment(height)[validator_id].contains(&shard_id)
+    }
+
+    /// Power of a validator relative to the validators assigned to the same
+    /// shard at given height.
+    pub fn shard_power(&mut self, shard_id: u64, validator_id: usize, height: u64) -> Float {
+        let power = self.power(shard_id, validator_id, height);
+
+        if self.runs_shard(shard_id) || power == 0 {
+            return power;
+        }
+
+        let assigned: Vec<_> = self
+            .shard_assignment(height)
+            .iter()
+            .map(|shards| shards.contains(&shard_id))
+            .collect();
+
+        let mut total = Float::with_val(FLOAT_PRECISION, 0);
+        for (validator_id, _) in assigned.into_iter().enumerate().filter(|(_, a)| *a) {
+            total += self.power(shard_id, validator_id, height);
+        }
+
+        power / total
+    }
+}
+
+/// Assign `shards` shards starting at `first_shard` to validators for an
+/// epoch of given seed. Validators are shuffled then given consecutive shards, so every
+/// shard has validators as long as `validators * shards_per_validator >= shards`.
+fn assign_shards(
+    seed: &[u8],
+    epoch: u64,
+    validators: usize,
+    first_shard: u64,
+    shards: u64,
+    shards_per_validator: u64,
+) -> Vec<BTreeSet<u64>> {
+    let domain = [b"shard assignment", seed].concat();
+    let keys: Vec<_> = (0..validators)
+        .map(|v| rng::uniform(&domain, &[epoch, v as u64]))
+        .collect();
+
+    let mut order: Vec<_> = (0..validators).collect();
+    order.sort_by(|a, b| keys[*a].partial_cmp(&keys[*b]).unwrap());
+
+    let mut assignments = vec![BTreeSet::new(); validators];
+
+    for (rank, &validator_id) in order.iter().enumerate() {
+        for i in 0..shards_per_validator {
+            let shard = (rank as u64 * shards_per_validator + i) % shards;
+            assignments[validator_id].insert(first_shard + shard);
+        }
+    }
+
+    assignments
+}